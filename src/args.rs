@@ -1,15 +1,22 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
-use crate::hashers::HashFunction;
+use crate::{
+    check::ManifestKind, dupes::LinkKind, formats::Format, hashers::HashFunction,
+    metadata::MetadataField,
+};
 
 /// Arguments for dircs.
 #[derive(Parser)]
 #[command(about = "A small cross-platform utility to get the hash of a file or directory.")]
 #[command(version)]
 #[command(arg_required_else_help = true)]
+#[command(args_conflicts_with_subcommands = true)]
 pub(crate) struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(
         num_args(1..),
         help = "The paths to run the dircs on.",
@@ -20,14 +27,30 @@ pub(crate) struct Args {
     #[arg(
         short = 'f',
         long,
-        help = "The hash function to use.",
-        default_value = "blake3"
+        global = true,
+        help = "The hash function to use. Defaults to blake3, unless --format requires something else."
+    )]
+    pub hash: Option<HashFunction>,
+
+    #[arg(
+        long,
+        help = "The format of the resulting hash.",
+        default_value = "dircs"
+    )]
+    pub format: Format,
+
+    #[cfg(feature = "sha2")]
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        help = "The prefix for file names when using the go format, usually `<module>@<version>`."
     )]
-    pub hash: HashFunction,
+    pub go_prefix: Option<String>,
 
     #[arg(
         short,
         long,
+        global = true,
         help = "The maximum recursion depth for file traversal. Will scan as deep as possible by default."
     )]
     pub depth: Option<usize>,
@@ -35,6 +58,7 @@ pub(crate) struct Args {
     #[arg(
         short = 't',
         long,
+        global = true,
         help = "The maximum number of CPU threads to use. Automatically chosen by default.",
         long_help = "The maximum number of CPU threads to use. Automatically chosen by default. Used for parallelizing file hashing and directory traversal."
     )]
@@ -43,14 +67,159 @@ pub(crate) struct Args {
     #[arg(
         short,
         long,
+        global = true,
         help = "Whether to skip hidden files. Does not skip by default.",
         default_value_t = false
     )]
     pub skip_hidden: bool,
 
+    #[arg(
+        long,
+        value_name = "GLOB",
+        global = true,
+        help = "Only hash files matching this glob. Can be specified multiple times.",
+        long_help = "Only hash files matching this glob. Can be specified multiple times, in which case a file only needs to match one glob. \
+Globs are matched against the path relative to the path being hashed, and `*` does not match across directories - use `**` for that (e.g. `src/**/*.rs`)."
+    )]
+    pub include: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        global = true,
+        help = "Skip files and directories matching this glob. Can be specified multiple times.",
+        long_help = "Skip files and directories matching this glob. Can be specified multiple times. \
+Globs are matched against the path relative to the path being hashed, and `*` does not match across directories - use `**` for that (e.g. `**/node_modules`). \
+Excluded directories are not traversed at all. Takes precedence over --include."
+    )]
+    pub exclude: Vec<String>,
+
     #[arg(
         short,
         long,
+        global = true,
+        help = "Whether to respect ignore files such as .gitignore. Disabled by default.",
+        long_help = "Whether to respect ignore files. Disabled by default. When enabled:
+* .gitignore, .ignore, and .dircsignore files are respected, including nested ones. All use the same gitignore syntax.
+* The repository's .git/info/exclude and git's global excludes file are respected.
+* .git directories are skipped.
+* The contents of directories with a valid CACHEDIR.TAG file are skipped.",
+        default_value_t = false
+    )]
+    pub ignore_files: bool,
+
+    #[cfg(feature = "git")]
+    #[arg(
+        long,
+        global = true,
+        help = "Only hash files tracked by the git repository the path is in.",
+        long_help = "Only hash files tracked by the git repository the path is in, based on the repository's index. \
+Files are still read from the working directory, so modifications to tracked files will change the hash.",
+        default_value_t = false
+    )]
+    pub git_tracked: bool,
+
+    #[cfg(feature = "git")]
+    #[arg(
+        long,
+        value_name = "REV",
+        help = "Hash the path as it is in a git revision, such as a commit or branch.",
+        long_help = "Hash the path as it is in a git revision, such as a commit, tag, or branch. \
+Files are read from the repository's object database, so untracked files and changes in the working directory are ignored. \
+The result is the same as hashing a clean checkout of that revision, except that symlinks are hashed as their target path.",
+        conflicts_with_all = ["ignore_files", "git_tracked", "memmap", "include_metadata", "cache", "sample", "offset", "length", "piecewise"]
+    )]
+    pub git_rev: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FIELDS",
+        value_delimiter = ',',
+        help = "Include file metadata in the hash, as a comma-separated list of fields.",
+        long_help = "Include file metadata in the hash, as a comma-separated list of fields (e.g. `mode,uid,gid`). \
+Each file's metadata is hashed along with its contents, so the hash changes if e.g. a file loses its executable bit. \
+The metadata of directories below the hashed path is included too. \
+Only supported by the dircs format."
+    )]
+    pub include_metadata: Vec<MetadataField>,
+
+    #[cfg(unix)]
+    #[arg(
+        long,
+        value_name = "NAMESPACE",
+        help = "Only include extended attributes in this namespace (e.g. `user` or `security`). Can be specified multiple times.",
+        long_help = "Only include extended attributes in this namespace (e.g. `user`, `security`, `trusted`, or `system`) \
+when `--include-metadata xattr` is used. Can be specified multiple times. All extended attributes are included by default.",
+        requires = "include_metadata"
+    )]
+    pub xattr_namespace: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Cache file hashes, so that unchanged files don't need to be hashed again.",
+        long_help = "Cache file hashes, so that unchanged files don't need to be hashed again. \
+The cache is stored in `$XDG_CACHE_HOME/dircs`, or `~/.cache/dircs` if that isn't set. \
+Files are considered unchanged if their device, inode, size, modification time, and change time are the same. \
+Files modified in the last couple of seconds aren't cached, as they could change again without their modification time changing. \
+Entries that haven't been used for 30 days are dropped. \
+Only supported on Unix, and only used by the dircs format.",
+        default_value_t = false
+    )]
+    pub cache: bool,
+
+    #[arg(
+        long,
+        value_name = "BLOCKS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "16",
+        value_parser = clap::value_parser!(u64).range(0..=65536),
+        help = "Only hash a sample of each large file, for a quick but non-exhaustive hash.",
+        long_help = "Only hash a sample of each large file, for a quick but non-exhaustive hash. \
+A sample is the file's size, its first and last 64 KiB, and BLOCKS evenly spaced 64 KiB blocks in between (16 by default, at most 65536, e.g. `--sample=32`). \
+Files that are smaller than the sample are hashed in full. Changes outside of the sampled blocks are NOT detected, so the output is labelled as sampled. \
+Only supported by the dircs format.",
+        conflicts_with = "cache"
+    )]
+    pub sample: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help = "Only hash each file's contents from this byte offset onwards.",
+        long_help = "Only hash each file's contents from this byte offset onwards, e.g. to skip a firmware image's header. \
+Files shorter than the offset are hashed as if they were empty. Only supported by the dircs format.",
+        default_value_t = 0,
+        conflicts_with_all = ["cache", "sample"]
+    )]
+    pub offset: u64,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help = "Only hash up to this many bytes of each file's contents, starting from --offset.",
+        long_help = "Only hash up to this many bytes of each file's contents, starting from --offset. \
+Files that end before then are hashed up to their end. Only supported by the dircs format.",
+        conflicts_with_all = ["cache", "sample"]
+    )]
+    pub length: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Also output the hash of every block of this many bytes in each file.",
+        long_help = "Also output the hash of every block of this many bytes in each file, like `hashdeep -p`. \
+Each block's hash is listed with the byte range it covers, followed by the whole file's hash, so that damaged regions of a large file can be located. \
+When hashing a directory, each file's hash is listed as well as the directory's. Only supported by the dircs format.",
+        conflicts_with_all = ["cache", "sample"]
+    )]
+    pub piecewise: Option<u64>,
+
+    #[arg(
+        short,
+        long,
+        global = true,
         help = "Whether to enable memmapping for reading files. Disabled by default. May use a lot of memory.",
         long_help = "Whether to enable memmapping for reading files. Disabled by default. Note that:
 * This uses a lot of memory, especially with multiple threads.
@@ -73,14 +242,246 @@ pub(crate) struct Args {
     #[arg(
         short,
         long,
+        global = true,
         help = "Whether to show verbose logging. Disabled by default.",
         default_value_t = false
     )]
     pub verbose: bool,
 }
 
+/// Subcommands for dircs.
+#[derive(Subcommand)]
+pub(crate) enum Command {
+    /// Verify files against checksum manifests from other tools.
+    Check {
+        #[arg(help = "The type of manifest to check against.")]
+        kind: ManifestKind,
+
+        #[arg(
+            num_args(1..),
+            required = true,
+            help = "The paths to check.",
+            long_help = "The paths to check. What these should point to depends on the type of manifest."
+        )]
+        paths: Vec<PathBuf>,
+
+        #[arg(
+            long,
+            help = "The directory that paths in detached manifests are relative to.",
+            long_help = "The directory that paths in detached manifests are relative to. \
+            This is for manifests that are stored separately from the files they list. For dpkg's \
+            `*.md5sums` files this defaults to `/`, and can be set to check an installed system \
+            mounted elsewhere. For torrents this defaults to the directory containing the `.torrent` \
+            file, and should be set to wherever the torrent was downloaded to."
+        )]
+        root: Option<PathBuf>,
+    },
+    /// Write a manifest of every file's hash and metadata, for use with `status`.
+    Manifest {
+        #[arg(help = "The path to make a manifest of.")]
+        path: PathBuf,
+
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            help = "Where to write the manifest. Defaults to stdout."
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Quickly list files that have changed since a manifest was made, using only metadata.
+    #[command(
+        long_about = "Quickly list files that have changed since a manifest was made, using only metadata. \
+Files are considered changed if their size, modification time, or inode differ from the manifest. \
+Exits with an error if anything changed."
+    )]
+    Status {
+        #[arg(help = "The manifest to compare against, as written by `dircs manifest`.")]
+        manifest: PathBuf,
+
+        #[arg(help = "The path the manifest was made of.")]
+        path: PathBuf,
+
+        #[arg(
+            long,
+            help = "Hash files whose metadata changed, and only list them if their contents changed too.",
+            default_value_t = false
+        )]
+        confirm: bool,
+    },
+    /// Compare two files block by block, and list the byte ranges where they differ.
+    #[command(
+        long_about = "Compare two files block by block, and list the byte ranges where they differ. \
+Blocks of both files are read and compared in parallel, which is much faster than `cmp` for large files such as disk images. \
+Exits with an error if the files differ."
+    )]
+    Cmp {
+        #[arg(help = "The first file to compare.")]
+        a: PathBuf,
+
+        #[arg(help = "The second file to compare.")]
+        b: PathBuf,
+
+        #[arg(
+            long,
+            value_name = "BYTES",
+            value_parser = clap::value_parser!(u64).range(1..=1 << 30),
+            default_value_t = 1024 * 1024,
+            help = "The size of the blocks to compare, up to 1 GiB. Differences are reported at this granularity."
+        )]
+        block_size: u64,
+
+        #[arg(
+            long,
+            help = "Stop at the first block that differs.",
+            default_value_t = false
+        )]
+        first: bool,
+    },
+    /// Split files into content-defined chunks, and report how much deduplication would save.
+    #[command(
+        long_about = "Split files into content-defined chunks, and report how much deduplication would save. \
+Files are split with FastCDC, so that chunk boundaries depend on the contents rather than on offsets, and each chunk is hashed. \
+The total size of all chunks is compared with the size of the unique chunks, across all of the given paths."
+    )]
+    Chunks {
+        #[arg(num_args(1..), required = true, help = "The paths to chunk.")]
+        paths: Vec<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "BYTES",
+            value_parser = clap::value_parser!(u32).range(256..=4 * 1024 * 1024),
+            default_value_t = 64 * 1024,
+            help = "The average chunk size, between 256 bytes and 4 MiB.",
+            long_help = "The average chunk size, between 256 bytes and 4 MiB. \
+Chunks are between a quarter of and four times this size. This should match the chunk size of the backend being sized."
+        )]
+        avg_size: u32,
+    },
+    /// Find files with the same contents, and report how much space they waste.
+    #[command(
+        long_about = "Find files with the same contents, and report how much space they waste. \
+Files are grouped by size, and only files that share a size are hashed. Empty files, and hardlinks to a file that was already found, are ignored."
+    )]
+    Dupes {
+        #[arg(num_args(1..), required = true, help = "The paths to search for duplicates.")]
+        paths: Vec<PathBuf>,
+
+        #[arg(
+            long,
+            help = "Output the duplicate sets as JSON.",
+            default_value_t = false
+        )]
+        json: bool,
+
+        #[arg(
+            long,
+            value_name = "KIND",
+            help = "Replace duplicates with links to the first file in their set.",
+            long_help = "Replace duplicates with links to the first file in their set. \
+Each duplicate is compared byte for byte with the first file before it's replaced.",
+            conflicts_with = "json"
+        )]
+        link: Option<LinkKind>,
+
+        #[arg(
+            long,
+            help = "Show which duplicates would be replaced by --link, without replacing them.",
+            default_value_t = false,
+            requires = "link"
+        )]
+        dry_run: bool,
+    },
+    /// Write and verify Bao outboard files, which allow verifying slices of a file.
+    Bao {
+        #[command(subcommand)]
+        command: BaoCommand,
+    },
+    /// Store each file's hash in its extended attributes, so that it can be scrubbed later.
+    #[cfg(unix)]
+    #[command(
+        long_about = "Store each file's hash in its extended attributes, so that it can be scrubbed later. \
+The hash, hash function, size, and modification time are stored in `user.dircs.*` attributes."
+    )]
+    Tag {
+        #[arg(num_args(1..), required = true, help = "The paths to tag.")]
+        paths: Vec<PathBuf>,
+    },
+    /// Re-hash tagged files and report any that have been silently corrupted.
+    #[cfg(unix)]
+    #[command(
+        long_about = "Re-hash tagged files and report any that have been silently corrupted. \
+Files that have been modified since they were tagged, going by their size and modification time, are skipped."
+    )]
+    Scrub {
+        #[arg(num_args(1..), required = true, help = "The paths to scrub.")]
+        paths: Vec<PathBuf>,
+    },
+}
+
+/// Subcommands for Bao outboard files.
+#[derive(Subcommand)]
+pub(crate) enum BaoCommand {
+    /// Write a Bao outboard file next to each file, and output each file's BLAKE3 hash.
+    #[command(
+        long_about = "Write a Bao outboard file next to each file, and output each file's BLAKE3 hash. \
+An outboard file stores the file's BLAKE3 hash tree, which lets any slice of the file be verified against the hash without reading the rest of the file. \
+Outboard files are named `<file>.obao`, and use the same format as `bao encode --outboard`."
+    )]
+    Encode {
+        #[arg(num_args(1..), required = true, help = "The paths to encode.")]
+        paths: Vec<PathBuf>,
+    },
+    /// Verify a slice of a file against its BLAKE3 hash, using its Bao outboard file.
+    #[command(
+        long_about = "Verify a slice of a file against its BLAKE3 hash, using its Bao outboard file. \
+Only the parts of the file and outboard file that cover the slice are read. Exits with an error if the slice doesn't match the hash."
+    )]
+    Verify {
+        #[arg(help = "The file to verify.")]
+        file: PathBuf,
+
+        #[arg(
+            value_name = "HASH",
+            help = "The file's BLAKE3 hash, as output by `dircs bao encode`."
+        )]
+        root_hash: String,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "The outboard file to use. Defaults to `<file>.obao`."
+        )]
+        outboard: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "BYTES",
+            help = "Where the slice to verify starts.",
+            default_value_t = 0
+        )]
+        offset: u64,
+
+        #[arg(
+            long,
+            value_name = "BYTES",
+            help = "The length of the slice to verify. Defaults to the rest of the file."
+        )]
+        length: Option<u64>,
+    },
+}
+
 impl Args {
     pub(crate) fn sort_args(&mut self) {
         self.paths.sort();
+        self.include_metadata.sort();
+        self.include_metadata.dedup();
+    }
+
+    /// The hash function to use, taking into account the format's default.
+    pub(crate) fn hash_function(&self) -> HashFunction {
+        self.hash
+            .unwrap_or_else(|| self.format.default_hash_function())
     }
 }
@@ -0,0 +1,298 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use blake3::{
+    CHUNK_LEN, Hasher,
+    hazmat::{
+        ChainingValue, HasherExt, Mode, left_subtree_len, merge_subtrees_non_root,
+        merge_subtrees_root,
+    },
+};
+use rayon::prelude::{ParallelBridge, ParallelIterator};
+
+use crate::{args::Args, hashers::HashFunction, memmap::try_memmap, walk::build_walker};
+
+/// The extension of outboard files.
+const EXTENSION: &str = "obao";
+/// The size of the length header at the start of an outboard file.
+const HEADER_LEN: usize = 8;
+/// The size of a parent node, which is the chaining values of its two children.
+const PARENT_LEN: usize = 64;
+/// Subtrees larger than this are encoded in parallel.
+const PARALLEL_LEN: usize = 128 * 1024;
+
+/// Write a Bao outboard file next to every file in the paths, and print each file's hash.
+pub(crate) fn encode(args: &Args, paths: &[PathBuf]) -> anyhow::Result<()> {
+    check_hash_function(args)?;
+
+    for path in paths {
+        let mut results = build_walker(args, path)?
+            .into_iter()
+            .enumerate()
+            .par_bridge()
+            .filter_map(|(index, entry)| match entry {
+                Ok(entry)
+                    if entry.file_type().is_file()
+                        && entry.path().extension().is_none_or(|ext| ext != EXTENSION) =>
+                {
+                    let path = entry.path();
+                    Some((index, encode_file(&path), path))
+                }
+                Ok(_) => None,
+                Err(err) => {
+                    if args.verbose {
+                        println!("Found an issue with entry {err:?}, skipping.");
+                    }
+
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        results.sort_by_key(|(index, ..)| *index);
+
+        for (_, result, path) in results {
+            match result {
+                Ok(hash) => println!("{} -> {}", path.to_string_lossy(), hash.to_hex()),
+                Err(err) => println!("{} -> {err}", path.to_string_lossy()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify a slice of a file against its hash, using its outboard file. Only the parts of
+/// the file and the outboard that cover the slice are read.
+pub(crate) fn verify(
+    args: &Args,
+    path: &Path,
+    root_hash: &str,
+    outboard: Option<&Path>,
+    offset: u64,
+    length: Option<u64>,
+) -> anyhow::Result<()> {
+    check_hash_function(args)?;
+
+    let hash = blake3::Hash::from_hex(root_hash).context("invalid BLAKE3 hash")?;
+    let outboard = match outboard {
+        Some(outboard) => outboard.to_path_buf(),
+        None => outboard_path(path),
+    };
+
+    let mut verifier = Verifier {
+        file: File::open(path)?,
+        outboard: File::open(&outboard)
+            .with_context(|| format!("couldn't open {}", outboard.to_string_lossy()))?,
+        start: offset,
+        end: 0,
+    };
+
+    let mut header = [0; HEADER_LEN];
+    verifier.outboard.read_exact(&mut header)?;
+    let len = u64::from_le_bytes(header);
+
+    if verifier.file.metadata()?.len() != len {
+        bail!("the file's size doesn't match its outboard");
+    }
+
+    verifier.end = match length {
+        Some(length) => offset.saturating_add(length),
+        None => len,
+    };
+    if verifier.end > len {
+        bail!("the slice is past the end of the file");
+    } else if verifier.start >= verifier.end && len > 0 {
+        bail!("the slice is empty");
+    }
+
+    verifier.verify(0, len, 0, *hash.as_bytes(), true)?;
+
+    println!(
+        "{}: OK ({} bytes at offset {})",
+        path.to_string_lossy(),
+        verifier.end - verifier.start,
+        verifier.start
+    );
+
+    Ok(())
+}
+
+fn check_hash_function(args: &Args) -> anyhow::Result<()> {
+    if !matches!(args.hash_function(), HashFunction::Blake3) {
+        bail!("bao only supports blake3");
+    }
+
+    Ok(())
+}
+
+fn outboard_path(path: &Path) -> PathBuf {
+    let mut outboard = path.as_os_str().to_owned();
+    outboard.push(".");
+    outboard.push(EXTENSION);
+
+    PathBuf::from(outboard)
+}
+
+/// The number of parent nodes in the tree for an input of this length.
+fn parent_count(len: u64) -> u64 {
+    len.div_ceil(CHUNK_LEN as u64).saturating_sub(1)
+}
+
+/// Write a file's outboard, returning its hash.
+fn encode_file(path: &Path) -> anyhow::Result<blake3::Hash> {
+    let file = File::open(path)?;
+    let mmap = try_memmap(&file)?;
+    let contents;
+    let data = match &mmap {
+        Some(mmap) => &mmap[..],
+        None => {
+            contents = fs::read(path)?;
+            &contents[..]
+        }
+    };
+
+    let outboard_len = HEADER_LEN + parent_count(data.len() as u64) as usize * PARENT_LEN;
+    let outboard = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(outboard_path(path))?;
+    outboard.set_len(outboard_len as u64)?;
+
+    // SAFETY: The outboard was just created, and is only written to through this map.
+    let mut outboard = unsafe { memmap2::MmapMut::map_mut(&outboard)? };
+    let (header, nodes) = outboard.split_at_mut(HEADER_LEN);
+    header.copy_from_slice(&(data.len() as u64).to_le_bytes());
+
+    let hash = if data.len() <= CHUNK_LEN {
+        blake3::hash(data)
+    } else {
+        let (left, right) = encode_children(data, 0, nodes);
+        merge_subtrees_root(&left, &right, Mode::Hash)
+    };
+
+    outboard.flush()?;
+
+    Ok(hash)
+}
+
+/// Encode the two children of a subtree with more than one chunk, writing the parent node
+/// followed by each child's nodes. Returns the children's chaining values.
+fn encode_children(data: &[u8], offset: u64, nodes: &mut [u8]) -> (ChainingValue, ChainingValue) {
+    let left_len = left_subtree_len(data.len() as u64) as usize;
+    let (node, nodes) = nodes.split_at_mut(PARENT_LEN);
+    let (left_nodes, right_nodes) =
+        nodes.split_at_mut(parent_count(left_len as u64) as usize * PARENT_LEN);
+
+    let (left_data, right_data) = data.split_at(left_len);
+    let right_offset = offset + left_len as u64;
+    let (left, right) = if data.len() > PARALLEL_LEN {
+        rayon::join(
+            || encode_subtree(left_data, offset, left_nodes),
+            || encode_subtree(right_data, right_offset, right_nodes),
+        )
+    } else {
+        (
+            encode_subtree(left_data, offset, left_nodes),
+            encode_subtree(right_data, right_offset, right_nodes),
+        )
+    };
+
+    node[..32].copy_from_slice(&left);
+    node[32..].copy_from_slice(&right);
+
+    (left, right)
+}
+
+/// Encode a non-root subtree, returning its chaining value.
+fn encode_subtree(data: &[u8], offset: u64, nodes: &mut [u8]) -> ChainingValue {
+    if data.len() <= CHUNK_LEN {
+        Hasher::new()
+            .set_input_offset(offset)
+            .update(data)
+            .finalize_non_root()
+    } else {
+        let (left, right) = encode_children(data, offset, nodes);
+        merge_subtrees_non_root(&left, &right, Mode::Hash)
+    }
+}
+
+/// Verifies the parts of a file's tree that cover a slice.
+struct Verifier {
+    file: File,
+    outboard: File,
+    start: u64,
+    end: u64,
+}
+
+impl Verifier {
+    /// Verify a subtree starting at `offset` against its expected chaining value or root
+    /// hash. `node` is the index of the subtree's parent node in the outboard.
+    fn verify(
+        &mut self,
+        offset: u64,
+        len: u64,
+        node: u64,
+        expected: [u8; 32],
+        root: bool,
+    ) -> anyhow::Result<()> {
+        if len <= CHUNK_LEN as u64 {
+            let mut chunk = vec![0; len as usize];
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.read_exact(&mut chunk)?;
+
+            let actual = if root {
+                *blake3::hash(&chunk).as_bytes()
+            } else {
+                Hasher::new()
+                    .set_input_offset(offset)
+                    .update(&chunk)
+                    .finalize_non_root()
+            };
+
+            if actual != expected {
+                bail!("bytes {offset}-{} don't match the hash", offset + len - 1);
+            }
+
+            return Ok(());
+        }
+
+        let mut parent = [0; PARENT_LEN];
+        self.outboard.seek(SeekFrom::Start(
+            HEADER_LEN as u64 + node * PARENT_LEN as u64,
+        ))?;
+        self.outboard.read_exact(&mut parent)?;
+
+        let (left, right) = parent.split_at(32);
+        let (left, right): (ChainingValue, ChainingValue) = (left.try_into()?, right.try_into()?);
+        let actual = if root {
+            *merge_subtrees_root(&left, &right, Mode::Hash).as_bytes()
+        } else {
+            merge_subtrees_non_root(&left, &right, Mode::Hash)
+        };
+
+        if actual != expected {
+            bail!("the outboard doesn't match the hash");
+        }
+
+        let left_len = left_subtree_len(len);
+        let right_offset = offset + left_len;
+
+        // Only descend into children that overlap the slice.
+        if self.start < right_offset {
+            self.verify(offset, left_len, node + 1, left, false)?;
+        }
+        if self.end > right_offset {
+            let right_node = node + 1 + parent_count(left_len);
+            self.verify(right_offset, len - left_len, right_node, right, false)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fs::{self, File, Metadata},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, anyhow};
+use clap::ValueEnum;
+
+use crate::{
+    TargetType,
+    hashers::{DircsHasher, HashFunction},
+};
+
+/// Files modified this recently might be modified again without their modification
+/// time changing, so they aren't cached.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+/// Entries that haven't been used for this long are dropped, so that files which were
+/// deleted, moved, or are never hashed again don't stay in the cache forever.
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// How often the last use of an entry is updated, so that the cache isn't rewritten on
+/// every run that only uses it.
+const LAST_USED_RESOLUTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What identifies a version of a file in the cache.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    dev: u64,
+    ino: u64,
+    size: u64,
+    mtime_ns: i128,
+    ctime_ns: i128,
+    algorithm: String,
+}
+
+impl Key {
+    #[cfg(unix)]
+    fn new(metadata: &Metadata, hash_function: HashFunction) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        let nanos = |secs: i64, nsecs: i64| i128::from(secs) * 1_000_000_000 + i128::from(nsecs);
+
+        Some(Self {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            size: metadata.len(),
+            mtime_ns: nanos(metadata.mtime(), metadata.mtime_nsec()),
+            ctime_ns: nanos(metadata.ctime(), metadata.ctime_nsec()),
+            algorithm: hash_function.to_possible_value()?.get_name().to_string(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn new(_metadata: &Metadata, _hash_function: HashFunction) -> Option<Self> {
+        None
+    }
+
+    /// Whether the file was modified so recently that it could change again without
+    /// its key changing.
+    fn is_racy(&self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(i128::MAX, |now| now.as_nanos() as i128);
+
+        now - self.mtime_ns.max(self.ctime_ns) < RACY_WINDOW.as_nanos() as i128
+    }
+
+    fn parse(line: &str) -> Option<(Self, Entry)> {
+        let mut fields = line.split(' ');
+        let mut next = || fields.next();
+
+        let key = Self {
+            dev: next()?.parse().ok()?,
+            ino: next()?.parse().ok()?,
+            size: next()?.parse().ok()?,
+            mtime_ns: next()?.parse().ok()?,
+            ctime_ns: next()?.parse().ok()?,
+            algorithm: next()?.to_string(),
+        };
+        let hash = hex::decode(next()?).ok()?;
+        // Entries written before this was tracked count as just used.
+        let last_used = next().map_or(Some(unix_time()), |secs| secs.parse().ok())?;
+
+        Some((key, Entry { hash, last_used }))
+    }
+}
+
+/// A cached hash.
+struct Entry {
+    hash: Vec<u8>,
+    /// When the entry was last used, in seconds since the Unix epoch.
+    last_used: u64,
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+/// A persistent cache of file hashes, so that unchanged files don't need to be re-hashed.
+///
+/// Files are identified by their device, inode, size, and modification and change times.
+/// The cache is stored as a text file with one entry per line, and entries that haven't
+/// been used for [`MAX_AGE`] are dropped when it's saved.
+///
+/// The cache isn't locked, so if several runs save it at the same time, the last one wins
+/// and the entries added by the others are lost. Those files are just hashed again later.
+pub(crate) struct HashCache {
+    path: PathBuf,
+    entries: HashMap<Key, Entry>,
+    used_entries: Mutex<Vec<Key>>,
+    new_entries: Mutex<Vec<(Key, Vec<u8>)>>,
+}
+
+impl HashCache {
+    /// Load the cache from `$XDG_CACHE_HOME/dircs`, or `~/.cache/dircs` if that isn't set.
+    pub(crate) fn load() -> anyhow::Result<Self> {
+        let cache_home = env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .ok_or_else(|| anyhow!("could not find a cache directory"))?;
+        let path = cache_home.join("dircs").join("hashes");
+
+        // A missing or corrupted cache just means we have to hash everything again.
+        let entries = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(Key::parse)
+            .collect();
+
+        Ok(Self {
+            path,
+            entries,
+            used_entries: Mutex::new(Vec::new()),
+            new_entries: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Hash a file, using the cached hash if the file hasn't changed. Returns the hash
+    /// and the number of bytes read, which is zero if the cached hash was used.
+    pub(crate) fn hash_file(
+        &self,
+        hasher: &DircsHasher,
+        hash_function: HashFunction,
+        file: File,
+        memmap: bool,
+    ) -> anyhow::Result<(Vec<u8>, usize)> {
+        let key = Key::new(&file.metadata()?, hash_function);
+
+        if let Some((key, entry)) = key.as_ref().and_then(|key| self.entries.get_key_value(key)) {
+            self.used_entries
+                .lock()
+                .map_err(|_| anyhow!("cache lock poisoned"))?
+                .push(key.clone());
+
+            return Ok((entry.hash.clone(), 0));
+        }
+
+        let (hash, bytes_read) = hasher.clone().hash_target(TargetType::new(file, memmap))?;
+
+        if let Some(key) = key.filter(|key| !key.is_racy()) {
+            self.new_entries
+                .lock()
+                .map_err(|_| anyhow!("cache lock poisoned"))?
+                .push((key, hash.clone()));
+        }
+
+        Ok((hash, bytes_read))
+    }
+
+    /// Write the cache back to disk, if anything in it changed. Older entries for files
+    /// that were re-hashed are dropped, along with entries that haven't been used recently.
+    pub(crate) fn save(self) -> anyhow::Result<()> {
+        let used_entries = self
+            .used_entries
+            .into_inner()
+            .map_err(|_| anyhow!("cache lock poisoned"))?;
+        let new_entries = self
+            .new_entries
+            .into_inner()
+            .map_err(|_| anyhow!("cache lock poisoned"))?;
+
+        let now = unix_time();
+        let mut entries = self.entries;
+        let entry_count = entries.len();
+        let mut changed = !new_entries.is_empty();
+
+        for key in &used_entries {
+            if let Some(entry) = entries.get_mut(key) {
+                if now.saturating_sub(entry.last_used) >= LAST_USED_RESOLUTION.as_secs() {
+                    entry.last_used = now;
+                    changed = true;
+                }
+            }
+        }
+
+        let replaced = new_entries
+            .iter()
+            .map(|(key, _)| (key.dev, key.ino, key.algorithm.as_str()))
+            .collect::<HashSet<_>>();
+        entries.retain(|key, entry| {
+            !replaced.contains(&(key.dev, key.ino, key.algorithm.as_str()))
+                && now.saturating_sub(entry.last_used) < MAX_AGE.as_secs()
+        });
+
+        if !changed && entries.len() == entry_count {
+            return Ok(());
+        }
+
+        entries.extend(new_entries.into_iter().map(|(key, hash)| {
+            (
+                key,
+                Entry {
+                    hash,
+                    last_used: now,
+                },
+            )
+        }));
+
+        let dir = self.path.parent().unwrap_or(&self.path);
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create {}", dir.to_string_lossy()))?;
+
+        // Write to a temporary file first, so that the cache is never left half-written.
+        let temp_path = self
+            .path
+            .with_extension(format!("tmp.{}", std::process::id()));
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+
+        for (key, entry) in &entries {
+            writeln!(
+                writer,
+                "{} {} {} {} {} {} {} {}",
+                key.dev,
+                key.ino,
+                key.size,
+                key.mtime_ns,
+                key.ctime_ns,
+                key.algorithm,
+                hex::encode(&entry.hash),
+                entry.last_used
+            )?;
+        }
+
+        writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+
+        Ok(())
+    }
+}
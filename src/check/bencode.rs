@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, bail};
+
+/// How deeply lists and dictionaries can be nested, to avoid overflowing the stack.
+const MAX_DEPTH: usize = 64;
+
+/// A bencoded value, as used by `.torrent` files.
+pub(super) enum Value {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    /// Parse a complete bencoded value.
+    pub(super) fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut parser = Parser { bytes, position: 0 };
+        let value = parser.value(0)?;
+
+        if parser.position != bytes.len() {
+            bail!("trailing data after bencoded value");
+        }
+
+        Ok(value)
+    }
+
+    /// Look up a key, if this is a dictionary.
+    pub(super) fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Dict(dict) => dict.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(int) => Some(*int),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_dict(&self) -> Option<&BTreeMap<Vec<u8>, Value>> {
+        match self {
+            Value::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self, depth: usize) -> anyhow::Result<Value> {
+        if depth > MAX_DEPTH {
+            bail!("bencoded value is nested too deeply");
+        }
+
+        match self.peek()? {
+            b'i' => {
+                self.position += 1;
+                Ok(Value::Int(self.int_until(b'e')?))
+            }
+            b'l' => {
+                self.position += 1;
+                let mut list = Vec::new();
+
+                while self.peek()? != b'e' {
+                    list.push(self.value(depth + 1)?);
+                }
+
+                self.position += 1;
+                Ok(Value::List(list))
+            }
+            b'd' => {
+                self.position += 1;
+                let mut dict = BTreeMap::new();
+
+                while self.peek()? != b'e' {
+                    let key = self.bytes()?;
+                    let value = self.value(depth + 1)?;
+                    dict.insert(key, value);
+                }
+
+                self.position += 1;
+                Ok(Value::Dict(dict))
+            }
+            b'0'..=b'9' => Ok(Value::Bytes(self.bytes()?)),
+            byte => bail!("unexpected byte {byte:#04x} at offset {}", self.position),
+        }
+    }
+
+    fn bytes(&mut self) -> anyhow::Result<Vec<u8>> {
+        let length = usize::try_from(self.int_until(b':')?)?;
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|&end| end <= self.bytes.len())
+            .context("unexpected end of bencoded data")?;
+
+        let bytes = self.bytes[self.position..end].to_vec();
+        self.position = end;
+
+        Ok(bytes)
+    }
+
+    fn int_until(&mut self, terminator: u8) -> anyhow::Result<i64> {
+        let start = self.position;
+        let length = self.bytes[start..]
+            .iter()
+            .position(|&byte| byte == terminator)
+            .context("unexpected end of bencoded data")?;
+
+        self.position += length + 1;
+
+        std::str::from_utf8(&self.bytes[start..start + length])?
+            .parse()
+            .with_context(|| format!("invalid integer at offset {start}"))
+    }
+
+    fn peek(&self) -> anyhow::Result<u8> {
+        self.bytes
+            .get(self.position)
+            .copied()
+            .context("unexpected end of bencoded data")
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use jwalk::WalkDir;
+use serde::Deserialize;
+
+use super::{CheckReport, ExpectedFile};
+use crate::hashers::HashFunction;
+
+const CHECKSUM_FILE: &str = ".cargo-checksum.json";
+
+#[derive(Deserialize)]
+struct CargoChecksums {
+    files: BTreeMap<String, String>,
+}
+
+/// Load the `.cargo-checksum.json` files for every vendored crate in the given paths.
+pub(super) fn load(paths: &[PathBuf]) -> anyhow::Result<(Vec<CheckReport>, Vec<ExpectedFile>)> {
+    let mut crate_dirs = Vec::new();
+
+    for path in paths {
+        if path.join(CHECKSUM_FILE).is_file() {
+            crate_dirs.push(path.clone());
+            continue;
+        }
+
+        let mut children = fs::read_dir(path)
+            .with_context(|| format!("could not read {}", path.to_string_lossy()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|child| child.join(CHECKSUM_FILE).is_file())
+            .collect::<Vec<_>>();
+
+        if children.is_empty() {
+            bail!(
+                "no {CHECKSUM_FILE} files found in {}",
+                path.to_string_lossy()
+            );
+        }
+
+        children.sort();
+        crate_dirs.extend(children);
+    }
+
+    let mut reports = Vec::with_capacity(crate_dirs.len());
+    let mut expected_files = Vec::new();
+
+    for crate_dir in crate_dirs {
+        let checksum_path = crate_dir.join(CHECKSUM_FILE);
+        let checksums: CargoChecksums = serde_json::from_slice(&fs::read(&checksum_path)?)
+            .with_context(|| format!("could not parse {}", checksum_path.to_string_lossy()))?;
+
+        let name = crate_dir
+            .file_name()
+            .unwrap_or(crate_dir.as_os_str())
+            .to_string_lossy()
+            .into_owned();
+        let mut report = CheckReport::new(name);
+        report.unlisted = unlisted_files(&crate_dir, &checksums);
+
+        for (name, hash) in checksums.files {
+            let hash = hex::decode(&hash).with_context(|| {
+                format!(
+                    "invalid hash for {name} in {}",
+                    checksum_path.to_string_lossy()
+                )
+            })?;
+            let path = crate_dir.join(&name);
+
+            expected_files.push(ExpectedFile {
+                report: reports.len(),
+                name: PathBuf::from(name),
+                path,
+                hash_function: HashFunction::Sha2_256,
+                hash,
+                size: None,
+            });
+        }
+
+        reports.push(report);
+    }
+
+    Ok((reports, expected_files))
+}
+
+/// Find files in the crate that aren't listed in its checksum file.
+fn unlisted_files(crate_dir: &Path, checksums: &CargoChecksums) -> Vec<PathBuf> {
+    let listed = checksums
+        .files
+        .keys()
+        .map(|name| name.as_str())
+        .collect::<HashSet<_>>();
+
+    WalkDir::new(crate_dir)
+        .sort(true)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let relative_path = path.strip_prefix(crate_dir).ok()?;
+
+            // Checksum files always use forward slashes.
+            let name = relative_path
+                .iter()
+                .map(|component| component.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            (name != CHECKSUM_FILE && !listed.contains(name.as_str()))
+                .then(|| relative_path.to_path_buf())
+        })
+        .collect()
+}
@@ -0,0 +1,106 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+
+use super::{CheckReport, ExpectedFile};
+use crate::{bytes_to_path, hashers::HashFunction};
+
+/// Load Debian `md5sums` files from the given paths. Manifests that aren't part of an
+/// unpacked package list paths relative to `root`.
+pub(super) fn load(
+    paths: &[PathBuf],
+    root: &Path,
+) -> anyhow::Result<(Vec<CheckReport>, Vec<ExpectedFile>)> {
+    // Pairs of manifests and the directory their paths are relative to.
+    let mut manifests = Vec::new();
+
+    for path in paths {
+        let package_manifest = path.join("DEBIAN").join("md5sums");
+
+        if path.is_file() {
+            manifests.push((path.clone(), root.to_path_buf()));
+        } else if package_manifest.is_file() {
+            manifests.push((package_manifest, path.clone()));
+        } else {
+            let mut children = fs::read_dir(path)
+                .with_context(|| format!("could not read {}", path.to_string_lossy()))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|child| {
+                    child
+                        .extension()
+                        .is_some_and(|extension| extension == "md5sums")
+                        && child.is_file()
+                })
+                .collect::<Vec<_>>();
+
+            if children.is_empty() {
+                bail!("no md5sums files found in {}", path.to_string_lossy());
+            }
+
+            children.sort();
+            manifests.extend(
+                children
+                    .into_iter()
+                    .map(|child| (child, root.to_path_buf())),
+            );
+        }
+    }
+
+    let mut reports = Vec::with_capacity(manifests.len());
+    let mut expected_files = Vec::new();
+
+    for (manifest_path, base) in manifests {
+        let manifest = fs::read(&manifest_path)
+            .with_context(|| format!("could not read {}", manifest_path.to_string_lossy()))?;
+
+        for (line_number, line) in manifest.split(|&byte| byte == b'\n').enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || {
+                format!(
+                    "invalid entry on line {} of {}",
+                    line_number + 1,
+                    manifest_path.to_string_lossy()
+                )
+            };
+
+            // Lines are `<hex>  <path>`, where the path may contain spaces.
+            let (Some(hash), Some(name)) = (line.get(..32), line.get(34..)) else {
+                bail!(invalid());
+            };
+            let hash = hex::decode(hash).with_context(invalid)?;
+            let name = bytes_to_path(name).with_context(invalid)?;
+
+            expected_files.push(ExpectedFile {
+                report: reports.len(),
+                path: base.join(name.strip_prefix("/").unwrap_or(&name)),
+                name,
+                hash_function: HashFunction::MD5,
+                hash,
+                size: None,
+            });
+        }
+
+        reports.push(CheckReport::new(package_name(&manifest_path, &base)));
+    }
+
+    Ok((reports, expected_files))
+}
+
+/// The name of the package a manifest belongs to.
+fn package_name(manifest_path: &Path, base: &Path) -> String {
+    let name = if manifest_path.ends_with("DEBIAN/md5sums") {
+        base.file_name()
+    } else {
+        manifest_path.file_stem()
+    };
+
+    name.unwrap_or(manifest_path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
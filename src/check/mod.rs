@@ -0,0 +1,245 @@
+#[cfg(all(feature = "sha1", feature = "sha2"))]
+mod bencode;
+#[cfg(feature = "sha2")]
+mod cargo;
+#[cfg(feature = "md5")]
+mod md5sums;
+#[cfg(feature = "sha2")]
+mod oci;
+#[cfg(feature = "sha2")]
+mod record;
+#[cfg(all(feature = "sha1", feature = "sha2"))]
+mod torrent;
+
+use std::{
+    fs::File,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+use clap::ValueEnum;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    TargetType,
+    args::Args,
+    hashers::{DircsHasher, HashFunction},
+};
+
+/// The types of manifests that can be checked against.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub(crate) enum ManifestKind {
+    /// `.cargo-checksum.json` files created by `cargo vendor`. Paths should either be a
+    /// vendor directory or a single vendored crate.
+    #[cfg(feature = "sha2")]
+    Cargo,
+    /// Python `*.dist-info/RECORD` files. Paths should either be a `site-packages`
+    /// directory or a single `.dist-info` directory.
+    #[cfg(feature = "sha2")]
+    Record,
+    /// Debian `md5sums` files. Paths should either be an unpacked package containing
+    /// `DEBIAN/md5sums`, a `*.md5sums` file, or a directory of them such as
+    /// `/var/lib/dpkg/info`. The latter two are relative to `--root`.
+    #[cfg(feature = "md5")]
+    Md5sums,
+    /// OCI image layouts. Every blob is checked against its digest, and every blob
+    /// referenced by the layout's manifests must exist with the right size.
+    #[cfg(feature = "sha2")]
+    Oci,
+    /// `.torrent` files, supporting both v1 and v2 torrents. Downloaded files are
+    /// expected to be alongside the `.torrent` file, or in `--root` if set.
+    #[cfg(all(feature = "sha1", feature = "sha2"))]
+    Torrent,
+}
+
+/// A file listed in a manifest, along with its expected hash.
+pub(crate) struct ExpectedFile {
+    /// The index of the report this file belongs to.
+    report: usize,
+    /// The path as listed in the manifest.
+    name: PathBuf,
+    /// The path on disk.
+    path: PathBuf,
+    hash_function: HashFunction,
+    hash: Vec<u8>,
+    /// The expected size, for manifests that list it.
+    size: Option<u64>,
+}
+
+/// The result of checking files against a single manifest.
+#[derive(Default)]
+pub(crate) struct CheckReport {
+    /// What was checked, such as the name of a crate.
+    name: String,
+    checked: usize,
+    modified: Vec<PathBuf>,
+    missing: Vec<PathBuf>,
+    unlisted: Vec<PathBuf>,
+    /// Files that couldn't be read, along with why.
+    errors: Vec<(PathBuf, String)>,
+}
+
+impl CheckReport {
+    #[cfg(any(feature = "md5", feature = "sha2"))]
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        self.modified.is_empty()
+            && self.missing.is_empty()
+            && self.unlisted.is_empty()
+            && self.errors.is_empty()
+    }
+
+    fn print(&self, verbose: bool) {
+        let name = &self.name;
+
+        if self.is_ok() {
+            if verbose {
+                println!("{name}: OK ({} files)", self.checked);
+            }
+
+            return;
+        }
+
+        println!("{name}: FAILED");
+
+        for (label, paths) in [
+            ("modified", &self.modified),
+            ("missing", &self.missing),
+            ("unlisted", &self.unlisted),
+        ] {
+            for path in paths {
+                println!("  {label}: {}", path.to_string_lossy());
+            }
+        }
+
+        for (path, err) in &self.errors {
+            println!("  error: {}: {err}", path.to_string_lossy());
+        }
+    }
+}
+
+enum FileStatus {
+    Ok,
+    Modified,
+    Missing,
+    /// The file exists, but couldn't be read.
+    Error(String),
+}
+
+/// Check paths against a type of manifest, printing the results. Returns an error if
+/// any check failed.
+pub(crate) fn run(
+    args: &Args,
+    kind: ManifestKind,
+    paths: &[PathBuf],
+    root: Option<&Path>,
+) -> anyhow::Result<()> {
+    let (mut reports, expected_files) = load(kind, paths, root)?;
+
+    let statuses = expected_files
+        .into_par_iter()
+        .map(|expected_file| {
+            let status = check_file(&expected_file, args.memmap);
+
+            if args.verbose {
+                let status = match &status {
+                    FileStatus::Ok => "OK".to_string(),
+                    FileStatus::Modified => "MODIFIED".to_string(),
+                    FileStatus::Missing => "MISSING".to_string(),
+                    FileStatus::Error(err) => format!("ERROR ({err})"),
+                };
+
+                println!("{:?}: {status}", expected_file.path);
+            }
+
+            (expected_file, status)
+        })
+        .collect::<Vec<_>>();
+
+    for (expected_file, status) in statuses {
+        let report = &mut reports[expected_file.report];
+        report.checked += 1;
+
+        match status {
+            FileStatus::Ok => {}
+            FileStatus::Modified => report.modified.push(expected_file.name),
+            FileStatus::Missing => report.missing.push(expected_file.name),
+            FileStatus::Error(err) => report.errors.push((expected_file.name, err)),
+        }
+    }
+
+    for report in &reports {
+        report.print(args.verbose);
+    }
+
+    let failed = reports.iter().filter(|report| !report.is_ok()).count();
+
+    if reports.len() == 1 {
+        println!("Checked 1 manifest, {failed} failed.");
+    } else {
+        println!("Checked {} manifests, {failed} failed.", reports.len());
+    }
+
+    if failed > 0 {
+        bail!("verification failed");
+    }
+
+    Ok(())
+}
+
+/// Load the manifests for the given paths, returning a report for each manifest and
+/// the files that they list. Manifests that can't be checked file by file are checked
+/// here instead, and don't list any files.
+#[cfg_attr(
+    not(any(feature = "md5", all(feature = "sha1", feature = "sha2"))),
+    allow(unused_variables)
+)]
+fn load(
+    kind: ManifestKind,
+    paths: &[PathBuf],
+    root: Option<&Path>,
+) -> anyhow::Result<(Vec<CheckReport>, Vec<ExpectedFile>)> {
+    match kind {
+        #[cfg(feature = "sha2")]
+        ManifestKind::Cargo => cargo::load(paths),
+        #[cfg(feature = "sha2")]
+        ManifestKind::Record => record::load(paths),
+        #[cfg(feature = "md5")]
+        ManifestKind::Md5sums => md5sums::load(paths, root.unwrap_or(Path::new("/"))),
+        #[cfg(feature = "sha2")]
+        ManifestKind::Oci => oci::load(paths),
+        #[cfg(all(feature = "sha1", feature = "sha2"))]
+        ManifestKind::Torrent => Ok((torrent::check(paths, root)?, Vec::new())),
+    }
+}
+
+fn check_file(expected_file: &ExpectedFile, memmap: bool) -> FileStatus {
+    let file = match File::open(&expected_file.path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return FileStatus::Missing,
+        Err(err) => return FileStatus::Error(err.to_string()),
+    };
+
+    if let Some(size) = expected_file.size {
+        // No need to hash a file if we already know it's different.
+        if file
+            .metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.len() != size)
+        {
+            return FileStatus::Modified;
+        }
+    }
+
+    match DircsHasher::new(expected_file.hash_function).hash_target(TargetType::new(file, memmap)) {
+        Ok((hash, _)) if hash == expected_file.hash => FileStatus::Ok,
+        Ok(_) => FileStatus::Modified,
+        Err(err) => FileStatus::Error(err.to_string()),
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+
+use super::{CheckReport, ExpectedFile};
+use crate::hashers::HashFunction;
+
+/// Media types of manifests that reference other descriptors.
+const MANIFEST_MEDIA_TYPES: [&str; 4] = [
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    #[serde(default)]
+    media_type: String,
+    digest: String,
+    size: u64,
+}
+
+/// The fields of indexes and manifests that reference other descriptors.
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+    config: Option<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+    #[serde(default)]
+    blobs: Vec<Descriptor>,
+    subject: Option<Descriptor>,
+}
+
+impl Manifest {
+    fn descriptors(self) -> impl Iterator<Item = Descriptor> {
+        self.manifests
+            .into_iter()
+            .chain(self.config)
+            .chain(self.layers)
+            .chain(self.blobs)
+            .chain(self.subject)
+    }
+}
+
+/// Load every blob in the given OCI image layouts, along with the sizes that the
+/// layouts' manifests expect them to have.
+pub(super) fn load(paths: &[PathBuf]) -> anyhow::Result<(Vec<CheckReport>, Vec<ExpectedFile>)> {
+    let mut reports = Vec::with_capacity(paths.len());
+    let mut expected_files = Vec::new();
+
+    for layout in paths {
+        if !layout.join("oci-layout").is_file() {
+            bail!("{} is not an OCI image layout", layout.to_string_lossy());
+        }
+
+        // Blobs keyed by their path relative to the layout, with their expected size
+        // if something references them.
+        let mut blobs = BTreeMap::new();
+
+        for algorithm_dir in read_dir_sorted(&layout.join("blobs"))? {
+            let Some(algorithm) = algorithm_dir.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            // Blobs using algorithms we don't know about can't be verified.
+            if hash_function(algorithm).is_none() {
+                continue;
+            }
+
+            for blob in read_dir_sorted(&algorithm_dir)? {
+                if let Some(name) = blob.file_name().and_then(|name| name.to_str()) {
+                    blobs.insert(format!("blobs/{algorithm}/{name}"), None);
+                }
+            }
+        }
+
+        let index_path = layout.join("index.json");
+        let index = read_manifest(&index_path)?
+            .with_context(|| format!("could not parse {}", index_path.to_string_lossy()))?;
+
+        let mut pending = index.descriptors().collect::<Vec<_>>();
+        let mut visited = HashSet::new();
+
+        while let Some(descriptor) = pending.pop() {
+            if !visited.insert(descriptor.digest.clone()) {
+                continue;
+            }
+
+            let name = blob_name(&descriptor.digest)?;
+            blobs.insert(name.clone(), Some(descriptor.size));
+
+            if MANIFEST_MEDIA_TYPES.contains(&descriptor.media_type.as_str()) {
+                // Broken manifests are reported when their digest doesn't match, so
+                // there's nothing to follow if they can't be read.
+                if let Ok(Some(manifest)) = read_manifest(&layout.join(&name)) {
+                    pending.extend(manifest.descriptors());
+                }
+            }
+        }
+
+        for (name, size) in blobs {
+            let Some((hash_function, hash)) = parse_blob_name(&name) else {
+                bail!("invalid blob {name} in {}", layout.to_string_lossy());
+            };
+
+            expected_files.push(ExpectedFile {
+                report: reports.len(),
+                path: layout.join(&name),
+                name: PathBuf::from(name),
+                hash_function,
+                hash,
+                size,
+            });
+        }
+
+        let name = layout
+            .file_name()
+            .unwrap_or(layout.as_os_str())
+            .to_string_lossy()
+            .into_owned();
+        reports.push(CheckReport::new(name));
+    }
+
+    Ok((reports, expected_files))
+}
+
+/// Read a manifest, returning `None` if it exists but isn't valid.
+fn read_manifest(path: &Path) -> anyhow::Result<Option<Manifest>> {
+    let bytes =
+        fs::read(path).with_context(|| format!("could not read {}", path.to_string_lossy()))?;
+
+    Ok(serde_json::from_slice(&bytes).ok())
+}
+
+fn read_dir_sorted(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut children = fs::read_dir(path)
+        .with_context(|| format!("could not read {}", path.to_string_lossy()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect::<Vec<_>>();
+
+    children.sort();
+    Ok(children)
+}
+
+fn hash_function(algorithm: &str) -> Option<HashFunction> {
+    match algorithm {
+        "sha256" => Some(HashFunction::Sha2_256),
+        "sha512" => Some(HashFunction::Sha2_512),
+        "blake3" => Some(HashFunction::Blake3),
+        _ => None,
+    }
+}
+
+/// Turn a digest like `sha256:<hex>` into the path of its blob.
+fn blob_name(digest: &str) -> anyhow::Result<String> {
+    match digest.split_once(':') {
+        Some((algorithm, hex))
+            if hash_function(algorithm).is_some() && !hex.contains(['/', '\\']) =>
+        {
+            Ok(format!("blobs/{algorithm}/{hex}"))
+        }
+        _ => bail!("unsupported digest `{digest}`"),
+    }
+}
+
+fn parse_blob_name(name: &str) -> Option<(HashFunction, Vec<u8>)> {
+    let mut components = name.split('/').skip(1);
+    let hash_function = hash_function(components.next()?)?;
+    let hash = hex::decode(components.next()?).ok()?;
+
+    Some((hash_function, hash))
+}
@@ -0,0 +1,147 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+use super::{CheckReport, ExpectedFile};
+use crate::hashers::HashFunction;
+
+const RECORD_FILE: &str = "RECORD";
+
+/// Load the `RECORD` files of every installed distribution in the given paths.
+pub(super) fn load(paths: &[PathBuf]) -> anyhow::Result<(Vec<CheckReport>, Vec<ExpectedFile>)> {
+    let mut dist_info_dirs = Vec::new();
+
+    for path in paths {
+        if is_dist_info(path) {
+            dist_info_dirs.push(path.clone());
+            continue;
+        }
+
+        let mut children = fs::read_dir(path)
+            .with_context(|| format!("could not read {}", path.to_string_lossy()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|child| is_dist_info(child))
+            .collect::<Vec<_>>();
+
+        if children.is_empty() {
+            bail!(
+                "no .dist-info directories found in {}",
+                path.to_string_lossy()
+            );
+        }
+
+        children.sort();
+        dist_info_dirs.extend(children);
+    }
+
+    let mut reports = Vec::with_capacity(dist_info_dirs.len());
+    let mut expected_files = Vec::new();
+
+    for dist_info_dir in dist_info_dirs {
+        let record_path = dist_info_dir.join(RECORD_FILE);
+        let record = fs::read_to_string(&record_path)
+            .with_context(|| format!("could not read {}", record_path.to_string_lossy()))?;
+
+        // Paths in a RECORD are relative to the directory the `.dist-info` is in.
+        let site_packages = dist_info_dir.parent().unwrap_or(Path::new(""));
+
+        for (line_number, line) in record.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || {
+                format!(
+                    "invalid entry on line {} of {}",
+                    line_number + 1,
+                    record_path.to_string_lossy()
+                )
+            };
+
+            let [name, hash, size] = <[String; 3]>::try_from(split_csv_line(line))
+                .map_err(|_| anyhow::anyhow!(invalid()))?;
+
+            // The RECORD itself and generated files like bytecode don't have a hash.
+            if hash.is_empty() {
+                continue;
+            }
+
+            let (hash_function, hash) = parse_hash(&hash).with_context(invalid)?;
+            let size = if size.is_empty() {
+                None
+            } else {
+                Some(size.parse().with_context(invalid)?)
+            };
+
+            expected_files.push(ExpectedFile {
+                report: reports.len(),
+                path: site_packages.join(&name),
+                name: PathBuf::from(name),
+                hash_function,
+                hash,
+                size,
+            });
+        }
+
+        let name = dist_info_dir
+            .file_name()
+            .unwrap_or(dist_info_dir.as_os_str())
+            .to_string_lossy()
+            .into_owned();
+        reports.push(CheckReport::new(name));
+    }
+
+    Ok((reports, expected_files))
+}
+
+fn is_dist_info(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "dist-info")
+        && path.join(RECORD_FILE).is_file()
+}
+
+/// Parse a hash in the form `<algorithm>=<urlsafe base64>`.
+fn parse_hash(hash: &str) -> anyhow::Result<(HashFunction, Vec<u8>)> {
+    let Some((algorithm, digest)) = hash.split_once('=') else {
+        bail!("missing hash algorithm");
+    };
+
+    let hash_function = match algorithm {
+        "sha256" => HashFunction::Sha2_256,
+        "sha384" => HashFunction::Sha2_384,
+        "sha512" => HashFunction::Sha2_512,
+        _ => bail!("unsupported hash algorithm `{algorithm}`"),
+    };
+
+    // Padding isn't allowed, but be lenient in case a tool added it anyway.
+    let digest = URL_SAFE_NO_PAD.decode(digest.trim_end_matches('='))?;
+
+    Ok((hash_function, digest))
+}
+
+/// Split a line of CSV into its fields, handling quoted fields.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(char),
+        }
+    }
+
+    fields.push(field);
+    fields
+}
@@ -0,0 +1,488 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use super::{CheckReport, bencode::Value};
+use crate::{
+    bytes_to_path,
+    hashers::{DircsHasher, HashFunction},
+};
+
+/// The size of the blocks at the bottom of a v2 torrent's merkle trees.
+const BLOCK_SIZE: u64 = 16 * 1024;
+/// The largest piece length that's accepted, as each piece is read into memory at once.
+const MAX_PIECE_LENGTH: u64 = 64 * 1024 * 1024;
+
+/// A file described by a torrent.
+struct TorrentFile {
+    /// The path as listed in the torrent.
+    name: PathBuf,
+    /// The path on disk.
+    path: PathBuf,
+    length: u64,
+    /// Padding files are never read, and only contain zeros.
+    padding: bool,
+    /// The root of the file's merkle tree, for v2 torrents.
+    pieces_root: Option<Vec<u8>>,
+}
+
+/// Verify the files described by each `.torrent` file. Files are expected to be in
+/// `root`, or alongside the `.torrent` file if not set.
+///
+/// Pieces can span files, so unlike other manifests these are verified while loading.
+/// Hybrid torrents are verified using their v2 metadata.
+pub(super) fn check(paths: &[PathBuf], root: Option<&Path>) -> anyhow::Result<Vec<CheckReport>> {
+    let mut reports = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let torrent = Value::parse(
+            &fs::read(path)
+                .with_context(|| format!("could not read {}", path.to_string_lossy()))?,
+        )
+        .with_context(|| format!("could not parse {}", path.to_string_lossy()))?;
+
+        let base = root
+            .or_else(|| path.parent())
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+
+        let report = check_torrent(&torrent, &base)
+            .with_context(|| format!("invalid torrent {}", path.to_string_lossy()))?;
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
+
+fn check_torrent(torrent: &Value, base: &Path) -> anyhow::Result<CheckReport> {
+    let info = torrent.get("info").context("missing info dictionary")?;
+    let name = info
+        .get("name")
+        .and_then(Value::as_bytes)
+        .context("missing name")?;
+    let piece_length = info
+        .get("piece length")
+        .and_then(Value::as_int)
+        .and_then(|piece_length| u64::try_from(piece_length).ok())
+        .filter(|&piece_length| piece_length > 0)
+        .context("missing piece length")?;
+
+    if !piece_length.is_power_of_two() || piece_length > MAX_PIECE_LENGTH {
+        bail!("invalid piece length {piece_length}");
+    }
+
+    let name = path_component(name)?;
+    let mut report = CheckReport::new(name.to_string_lossy().into_owned());
+
+    let (files, failed_files) = if let Some(file_tree) = info.get("file tree") {
+        let piece_layers = torrent.get("piece layers");
+        let files = v2_files(file_tree, base, &name)?;
+        let failed_files = check_v2(&files, piece_length, piece_layers)?;
+
+        (files, failed_files)
+    } else {
+        let files = v1_files(info, base, &name)?;
+        let pieces = info
+            .get("pieces")
+            .and_then(Value::as_bytes)
+            .context("missing pieces")?;
+        let failed_files = check_v1(&files, piece_length, pieces)?;
+
+        (files, failed_files)
+    };
+
+    for (file, failed) in files.into_iter().zip(failed_files) {
+        if file.padding {
+            continue;
+        }
+
+        report.checked += 1;
+
+        if !file.path.is_file() {
+            report.missing.push(file.name);
+        } else if failed {
+            report.modified.push(file.name);
+        }
+    }
+
+    Ok(report)
+}
+
+/// List the files in a v1 torrent.
+fn v1_files(info: &Value, base: &Path, name: &Path) -> anyhow::Result<Vec<TorrentFile>> {
+    let length = |value: &Value| {
+        value
+            .get("length")
+            .and_then(Value::as_int)
+            .and_then(|length| u64::try_from(length).ok())
+            .context("missing file length")
+    };
+
+    let Some(files) = info.get("files") else {
+        return Ok(vec![TorrentFile {
+            name: name.to_path_buf(),
+            path: base.join(name),
+            length: length(info)?,
+            padding: false,
+            pieces_root: None,
+        }]);
+    };
+
+    let files = files.as_list().context("invalid files list")?;
+    let mut torrent_files = Vec::with_capacity(files.len());
+
+    for file in files {
+        let components = file
+            .get("path")
+            .and_then(Value::as_list)
+            .context("missing file path")?;
+
+        let mut relative_path = PathBuf::new();
+        for component in components {
+            relative_path.push(path_component(
+                component.as_bytes().context("invalid file path")?,
+            )?);
+        }
+
+        let padding = file
+            .get("attr")
+            .and_then(Value::as_bytes)
+            .is_some_and(|attr| attr.contains(&b'p'));
+
+        torrent_files.push(TorrentFile {
+            path: base.join(name).join(&relative_path),
+            name: relative_path,
+            length: length(file)?,
+            padding,
+            pieces_root: None,
+        });
+    }
+
+    Ok(torrent_files)
+}
+
+/// List the files in a v2 torrent's file tree.
+fn v2_files(file_tree: &Value, base: &Path, name: &Path) -> anyhow::Result<Vec<TorrentFile>> {
+    fn collect(
+        tree: &Value,
+        relative_path: &mut PathBuf,
+        files: &mut Vec<TorrentFile>,
+    ) -> anyhow::Result<()> {
+        for (key, value) in tree.as_dict().context("invalid file tree")? {
+            // An empty key marks a file, and holds its details.
+            if key.is_empty() {
+                let length = value
+                    .get("length")
+                    .and_then(Value::as_int)
+                    .and_then(|length| u64::try_from(length).ok())
+                    .context("missing file length")?;
+                let pieces_root = value
+                    .get("pieces root")
+                    .and_then(Value::as_bytes)
+                    .map(<[u8]>::to_vec);
+
+                if length > 0 && pieces_root.as_ref().is_none_or(|root| root.len() != 32) {
+                    bail!(
+                        "missing pieces root for {}",
+                        relative_path.to_string_lossy()
+                    );
+                }
+
+                files.push(TorrentFile {
+                    name: relative_path.clone(),
+                    path: PathBuf::new(),
+                    length,
+                    padding: false,
+                    pieces_root,
+                });
+                continue;
+            }
+
+            relative_path.push(path_component(key)?);
+            collect(value, relative_path, files)?;
+            relative_path.pop();
+        }
+
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    collect(file_tree, &mut PathBuf::new(), &mut files)?;
+
+    // A torrent with a single file at the top level is stored without a directory.
+    let is_single_file = files.len() == 1 && files[0].name.components().count() == 1;
+
+    for file in &mut files {
+        file.path = if is_single_file {
+            base.join(&file.name)
+        } else {
+            base.join(name).join(&file.name)
+        };
+    }
+
+    Ok(files)
+}
+
+/// Verify v1 pieces, which are hashes of fixed-size chunks of all files concatenated
+/// together. Returns whether each file is part of a piece that failed.
+fn check_v1(files: &[TorrentFile], piece_length: u64, pieces: &[u8]) -> anyhow::Result<Vec<bool>> {
+    let total_length = files
+        .iter()
+        .try_fold(0u64, |total, file| total.checked_add(file.length))
+        .context("the files are too large")?;
+    let piece_count = total_length.div_ceil(piece_length);
+
+    if pieces.len() % 20 != 0 || pieces.len() as u64 / 20 != piece_count {
+        bail!("expected {piece_count} pieces");
+    }
+
+    let offsets = files
+        .iter()
+        .scan(0, |offset, file| {
+            let start = *offset;
+            *offset += file.length;
+            Some(start)
+        })
+        .collect::<Vec<_>>();
+
+    let failed_pieces = (0..piece_count)
+        .into_par_iter()
+        .map(|piece| {
+            let start = piece * piece_length;
+            let length = piece_length.min(total_length - start);
+            let mut buffer = vec![0; length as usize];
+
+            let expected = &pieces[piece as usize * 20..][..20];
+
+            read_span(files, &offsets, start, &mut buffer).is_err()
+                || DircsHasher::new(HashFunction::Sha1).hash_bytes(&buffer) != expected
+        })
+        .collect::<Vec<_>>();
+
+    Ok(files
+        .iter()
+        .zip(offsets)
+        .map(|(file, start)| {
+            if !has_length(file) {
+                return true;
+            }
+
+            if file.length == 0 {
+                return false;
+            }
+
+            let first_piece = start / piece_length;
+            let last_piece = (start + file.length - 1) / piece_length;
+
+            failed_pieces[first_piece as usize..=last_piece as usize]
+                .iter()
+                .any(|&failed| failed)
+        })
+        .collect())
+}
+
+/// Read bytes spanning multiple files, starting at an offset into all of them.
+fn read_span(
+    files: &[TorrentFile],
+    offsets: &[u64],
+    start: u64,
+    buffer: &mut [u8],
+) -> std::io::Result<()> {
+    let mut filled = 0;
+
+    for (file, &file_start) in files.iter().zip(offsets) {
+        let position = start + filled as u64;
+        let file_end = file_start + file.length;
+
+        if filled == buffer.len() {
+            break;
+        }
+
+        if file_end <= position {
+            continue;
+        }
+
+        let length = (file_end - position).min((buffer.len() - filled) as u64) as usize;
+        let chunk = &mut buffer[filled..filled + length];
+
+        if file.padding {
+            chunk.fill(0);
+        } else {
+            let mut handle = File::open(&file.path)?;
+            handle.seek(SeekFrom::Start(position - file_start))?;
+            handle.read_exact(chunk)?;
+        }
+
+        filled += length;
+    }
+
+    Ok(())
+}
+
+/// Verify v2 files against their merkle roots. Returns whether each file failed.
+fn check_v2(
+    files: &[TorrentFile],
+    piece_length: u64,
+    piece_layers: Option<&Value>,
+) -> anyhow::Result<Vec<bool>> {
+    if piece_length < BLOCK_SIZE {
+        bail!("invalid piece length {piece_length}");
+    }
+
+    // Piece layers aren't needed to compute the root, but a well-formed torrent has one
+    // for every file larger than a piece.
+    for file in files.iter().filter(|file| file.length > piece_length) {
+        let root = file.pieces_root.as_deref().unwrap_or_default();
+        let layer = piece_layers
+            .and_then(|layers| layers.as_dict())
+            .and_then(|layers| layers.get(root))
+            .and_then(Value::as_bytes);
+
+        if layer.is_none_or(|layer| layer.len() as u64 != file.length.div_ceil(piece_length) * 32) {
+            bail!("missing piece layer for {}", file.name.to_string_lossy());
+        }
+    }
+
+    let pieces = files
+        .iter()
+        .enumerate()
+        .filter(|(_, file)| has_length(file))
+        .flat_map(|(index, file)| {
+            (0..file.length.div_ceil(piece_length)).map(move |piece| (index, piece))
+        })
+        .collect::<Vec<_>>();
+
+    let piece_hashes = pieces
+        .into_par_iter()
+        .map(|(index, piece)| {
+            let file = &files[index];
+            let start = piece * piece_length;
+            let length = piece_length.min(file.length - start);
+
+            let read_piece = || -> std::io::Result<Vec<u8>> {
+                let mut buffer = vec![0; length as usize];
+                let mut handle = File::open(&file.path)?;
+                handle.seek(SeekFrom::Start(start))?;
+                handle.read_exact(&mut buffer)?;
+                Ok(buffer)
+            };
+
+            let hash = read_piece().ok().map(|buffer| {
+                let blocks = buffer
+                    .chunks(BLOCK_SIZE as usize)
+                    .map(sha256)
+                    .collect::<Vec<_>>();
+
+                // Files that fit in a single piece have a smaller tree.
+                let width = if file.length <= piece_length {
+                    blocks.len().next_power_of_two()
+                } else {
+                    (piece_length / BLOCK_SIZE) as usize
+                };
+
+                merkle_root(blocks, width, vec![0; 32])
+            });
+
+            (index, hash)
+        })
+        .collect::<Vec<_>>();
+
+    let piece_padding = merkle_root(
+        Vec::new(),
+        (piece_length / BLOCK_SIZE) as usize,
+        vec![0; 32],
+    );
+    let mut failed = vec![false; files.len()];
+    let mut file_pieces = vec![Vec::new(); files.len()];
+
+    // Pieces are kept in order, so each file's pieces end up in order too.
+    for (index, hash) in piece_hashes {
+        match hash {
+            Some(hash) => file_pieces[index].push(hash),
+            None => failed[index] = true,
+        }
+    }
+
+    for (index, file) in files.iter().enumerate() {
+        if !has_length(file) {
+            failed[index] = true;
+        }
+
+        if failed[index] || file.length == 0 {
+            continue;
+        }
+
+        let pieces = std::mem::take(&mut file_pieces[index]);
+        let piece_count = pieces.len();
+
+        let root = if piece_count == 1 {
+            pieces.into_iter().next().unwrap_or_default()
+        } else {
+            merkle_root(
+                pieces,
+                piece_count.next_power_of_two(),
+                piece_padding.clone(),
+            )
+        };
+
+        failed[index] = file.pieces_root.as_ref() != Some(&root);
+    }
+
+    Ok(failed)
+}
+
+/// Compute the root of a merkle tree with `width` leaves, where leaves past the end
+/// of `layer` are `padding`.
+fn merkle_root(mut layer: Vec<Vec<u8>>, mut width: usize, mut padding: Vec<u8>) -> Vec<u8> {
+    while width > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|pair| {
+                let mut hasher = DircsHasher::new(HashFunction::Sha2_256);
+                hasher.update(&pair[0]);
+                hasher.update(pair.get(1).unwrap_or(&padding));
+                hasher.finalize()
+            })
+            .collect();
+
+        let mut hasher = DircsHasher::new(HashFunction::Sha2_256);
+        hasher.update(&padding);
+        hasher.update(&padding);
+        padding = hasher.finalize();
+
+        width /= 2;
+    }
+
+    layer.pop().unwrap_or(padding)
+}
+
+fn sha256(bytes: &[u8]) -> Vec<u8> {
+    DircsHasher::new(HashFunction::Sha2_256).hash_bytes(bytes)
+}
+
+/// Whether a file exists on disk with the expected length.
+fn has_length(file: &TorrentFile) -> bool {
+    file.padding || fs::metadata(&file.path).is_ok_and(|metadata| metadata.len() == file.length)
+}
+
+/// Convert a path component from a torrent, making sure it can't escape the directory
+/// it's in.
+fn path_component(bytes: &[u8]) -> anyhow::Result<PathBuf> {
+    let component = bytes_to_path(bytes)?;
+
+    if bytes.is_empty()
+        || bytes == b"."
+        || bytes == b".."
+        || bytes.contains(&b'/')
+        || bytes.contains(&b'\\')
+    {
+        bail!("invalid path component {}", component.to_string_lossy());
+    }
+
+    Ok(component)
+}
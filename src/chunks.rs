@@ -0,0 +1,112 @@
+use std::{collections::HashSet, fs::File, path::PathBuf, sync::Mutex};
+
+use anyhow::bail;
+use fastcdc::v2020::StreamCDC;
+use rayon::prelude::{ParallelBridge, ParallelIterator};
+
+use crate::{args::Args, hashers::DircsHasher, walk::build_walker};
+
+/// Totals for the chunks that have been seen so far.
+#[derive(Default)]
+struct Totals {
+    files: u64,
+    failed: u64,
+    chunks: u64,
+    bytes: u64,
+    unique_chunks: u64,
+    unique_bytes: u64,
+    seen: HashSet<Vec<u8>>,
+}
+
+/// Split every file into content-defined chunks, and report how many bytes are unique
+/// across all of the paths.
+pub(crate) fn chunks(args: &Args, paths: &[PathBuf], avg_size: u32) -> anyhow::Result<()> {
+    let hash_function = args.hash_function();
+    let totals = Mutex::new(Totals::default());
+
+    for path in paths {
+        build_walker(args, path)?
+            .into_iter()
+            .par_bridge()
+            .for_each(|entry| {
+                let entry = match entry {
+                    Ok(entry) if entry.file_type().is_file() => entry,
+                    Ok(_) => return,
+                    Err(err) => {
+                        if args.verbose {
+                            println!("Found an issue with entry {err:?}, skipping.");
+                        }
+                        return;
+                    }
+                };
+                let path = entry.path();
+
+                // The chunker's minimum and maximum sizes are a quarter and four times the
+                // average size, as recommended by the FastCDC paper.
+                let result = File::open(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| {
+                        StreamCDC::new(file, avg_size / 4, avg_size, avg_size * 4)
+                            .map(|chunk| {
+                                let chunk = chunk?;
+                                let hash = DircsHasher::new(hash_function).hash_bytes(&chunk.data);
+
+                                Ok((hash, chunk.length as u64))
+                            })
+                            .collect::<anyhow::Result<Vec<_>>>()
+                    });
+
+                let mut totals = totals.lock().unwrap();
+                match result {
+                    Ok(chunks) => {
+                        if args.verbose {
+                            let bytes = chunks.iter().map(|(_, length)| length).sum::<u64>();
+                            println!("{path:?} -> {} chunks ({bytes} bytes read)", chunks.len());
+                        }
+
+                        totals.files += 1;
+
+                        for (hash, length) in chunks {
+                            totals.chunks += 1;
+                            totals.bytes += length;
+
+                            if totals.seen.insert(hash) {
+                                totals.unique_chunks += 1;
+                                totals.unique_bytes += length;
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        if args.verbose {
+                            println!("Couldn't process {path:?} because: `{err:?}`. Skipping.");
+                        }
+
+                        totals.failed += 1;
+                    }
+                }
+            });
+    }
+
+    let totals = totals.into_inner().unwrap();
+
+    if totals.files == 0 {
+        bail!("there were no files to chunk");
+    }
+
+    let saved = totals.bytes - totals.unique_bytes;
+    let percent = if totals.bytes == 0 {
+        0.0
+    } else {
+        saved as f64 / totals.bytes as f64 * 100.0
+    };
+
+    println!("Chunked {} files, {} failed.", totals.files, totals.failed);
+    println!("Total: {} bytes in {} chunks", totals.bytes, totals.chunks);
+    println!(
+        "Unique: {} bytes in {} chunks",
+        totals.unique_bytes, totals.unique_chunks
+    );
+    println!("Deduplication would save {saved} bytes ({percent:.1}%).");
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::{Context, anyhow, bail};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+/// Compare two files block by block, and print the byte ranges where they differ. Blocks
+/// are read and compared in parallel. If `first` is set, stop at the first difference.
+pub(crate) fn cmp(a: &Path, b: &Path, block_size: u64, first: bool) -> anyhow::Result<()> {
+    let size_a = file_size(a)?;
+    let size_b = file_size(b)?;
+    let size = size_a.max(size_b);
+    let blocks = size.div_ceil(block_size);
+
+    let block_differs = |files: &mut std::io::Result<(File, File, Vec<u8>, Vec<u8>)>,
+                         index: u64|
+     -> anyhow::Result<bool> {
+        let (file_a, file_b, buffer_a, buffer_b) = files
+            .as_mut()
+            .map_err(|err| anyhow!("couldn't open files: {err}"))?;
+        let offset = index * block_size;
+
+        read_block(file_a, offset, block_size, buffer_a)?;
+        read_block(file_b, offset, block_size, buffer_b)?;
+
+        Ok(buffer_a != buffer_b)
+    };
+    // Blocks are never larger than the files themselves.
+    let buffer_size = block_size.min(size) as usize;
+    let open_files = || {
+        Ok((
+            File::open(a)?,
+            File::open(b)?,
+            Vec::with_capacity(buffer_size),
+            Vec::with_capacity(buffer_size),
+        ))
+    };
+
+    let differing_blocks = if first {
+        (0..blocks)
+            .into_par_iter()
+            .map_init(open_files, |files, index| {
+                block_differs(files, index).map(|differs| (index, differs))
+            })
+            .find_first(|result| result.as_ref().map_or(true, |(_, differs)| *differs))
+            .transpose()?
+            .map(|(index, _)| index)
+            .into_iter()
+            .collect()
+    } else {
+        (0..blocks)
+            .into_par_iter()
+            .map_init(open_files, block_differs)
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .zip(0..)
+            .filter_map(|(differs, index)| differs.then_some(index))
+            .collect::<Vec<_>>()
+    };
+
+    let (a, b) = (a.to_string_lossy(), b.to_string_lossy());
+
+    if size_a != size_b {
+        println!("sizes differ: {a} is {size_a} bytes, {b} is {size_b} bytes");
+    }
+
+    if differing_blocks.is_empty() {
+        println!("{a} and {b} are identical.");
+        return Ok(());
+    }
+
+    // Merge consecutive blocks into ranges.
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for index in differing_blocks {
+        let start = index * block_size;
+        let end = size.min(start + block_size);
+
+        match ranges.last_mut() {
+            Some((_, last_end)) if *last_end == start => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    for (start, end) in &ranges {
+        println!("differ: bytes {start}-{}", end - 1);
+    }
+
+    if first {
+        bail!("{a} and {b} differ");
+    }
+
+    let differing_bytes = ranges.iter().map(|(start, end)| end - start).sum::<u64>();
+    bail!("{a} and {b} differ in {differing_bytes} of {size} bytes")
+}
+
+fn file_size(path: &Path) -> anyhow::Result<u64> {
+    let metadata = std::fs::metadata(path)
+        .with_context(|| format!("couldn't read {}", path.to_string_lossy()))?;
+
+    if !metadata.is_file() {
+        bail!("{} is not a file", path.to_string_lossy());
+    }
+
+    Ok(metadata.len())
+}
+
+/// Read up to `block_size` bytes at `offset` into `buffer`. Fewer bytes are read at the
+/// end of the file.
+fn read_block(
+    file: &mut File,
+    offset: u64,
+    block_size: u64,
+    buffer: &mut Vec<u8>,
+) -> anyhow::Result<()> {
+    buffer.clear();
+    file.seek(SeekFrom::Start(offset))?;
+    file.by_ref().take(block_size).read_to_end(buffer)?;
+
+    Ok(())
+}
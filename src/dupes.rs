@@ -0,0 +1,392 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, Metadata},
+    io::{ErrorKind, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+use clap::ValueEnum;
+use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use serde::{Serialize, Serializer};
+
+use crate::{TargetType, args::Args, hashers::DircsHasher, walk::build_walker};
+
+/// How to replace duplicate files.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub(crate) enum LinkKind {
+    /// Replace duplicates with hardlinks to the first file in their set. Duplicates whose
+    /// permissions or owner differ from the first file's are skipped.
+    Hardlink,
+    /// Replace duplicates with reflinks (copy-on-write clones) of the first file in their set,
+    /// keeping each duplicate's permissions, owner, and timestamps. Only supported by some
+    /// filesystems, such as Btrfs and XFS.
+    Reflink,
+}
+
+/// A set of files with the same contents.
+#[derive(Serialize)]
+struct DuplicateSet {
+    size: u64,
+    hash: String,
+    #[serde(serialize_with = "serialize_paths")]
+    paths: Vec<PathBuf>,
+}
+
+impl DuplicateSet {
+    /// The space used by every copy but the first.
+    fn wasted_bytes(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// The JSON output of `dupes`.
+#[derive(Serialize)]
+struct Report {
+    sets: Vec<DuplicateSet>,
+    duplicate_files: u64,
+    wasted_bytes: u64,
+}
+
+/// Find files with the same contents across all of the paths, and print each set of
+/// duplicates. Files are grouped by size first, so only files that share a size are hashed.
+/// If `link` is set, duplicates are then replaced with links to the first file in their set.
+pub(crate) fn dupes(
+    args: &Args,
+    paths: &[PathBuf],
+    json: bool,
+    link: Option<LinkKind>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let report = find_duplicates(args, paths)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    for set in &report.sets {
+        println!(
+            "{} files, {} bytes each ({} bytes wasted):",
+            set.paths.len(),
+            set.size,
+            set.wasted_bytes()
+        );
+
+        for path in &set.paths {
+            println!("  {}", path.to_string_lossy());
+        }
+
+        println!();
+    }
+
+    println!(
+        "Found {} duplicate sets with {} duplicate files, wasting {} bytes.",
+        report.sets.len(),
+        report.duplicate_files,
+        report.wasted_bytes
+    );
+
+    match link {
+        Some(kind) => link_duplicates(&report.sets, kind, dry_run),
+        None => Ok(()),
+    }
+}
+
+/// Replace every file in each set but the first with a link to the first. Each duplicate is
+/// compared byte for byte with the first file before it's replaced.
+fn link_duplicates(sets: &[DuplicateSet], kind: LinkKind, dry_run: bool) -> anyhow::Result<()> {
+    let results = sets
+        .par_iter()
+        .flat_map_iter(|set| {
+            let (original, duplicates) = set.paths.split_first().unwrap();
+
+            duplicates.iter().map(move |duplicate| {
+                let result = link_file(kind, original, duplicate, dry_run);
+                (set.size, original, duplicate, result)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    println!();
+
+    let (mut linked, mut reclaimed, mut failed) = (0, 0, 0);
+    for (size, original, duplicate, result) in results {
+        let (original, duplicate) = (original.to_string_lossy(), duplicate.to_string_lossy());
+
+        match result {
+            Ok(()) => {
+                if dry_run {
+                    println!("Would link {duplicate} -> {original}");
+                } else {
+                    println!("Linked {duplicate} -> {original}");
+                }
+
+                linked += 1;
+                reclaimed += size;
+            }
+            Err(err) => {
+                println!("Couldn't link {duplicate}: {err}");
+                failed += 1;
+            }
+        }
+    }
+
+    let verb = if dry_run { "Would link" } else { "Linked" };
+    println!("{verb} {linked} files, reclaiming {reclaimed} bytes, {failed} failed.");
+
+    if failed > 0 {
+        bail!("some duplicates couldn't be linked");
+    }
+
+    Ok(())
+}
+
+/// Replace `duplicate` with a link to `original`, if their contents are the same. The link
+/// is made next to the duplicate, and then renamed over it.
+fn link_file(
+    kind: LinkKind,
+    original: &Path,
+    duplicate: &Path,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    if !same_contents(original, duplicate)? {
+        bail!("its contents differ from {}", original.to_string_lossy());
+    }
+
+    let metadata = fs::metadata(duplicate)?;
+    if matches!(kind, LinkKind::Hardlink) && !same_owner(&fs::metadata(original)?, &metadata) {
+        bail!(
+            "its permissions or owner differ from {}",
+            original.to_string_lossy()
+        );
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    let temp_path = create_link(kind, original, duplicate)?;
+
+    // The temporary link is ours from here on, so it's safe to remove if anything fails.
+    let result = match kind {
+        LinkKind::Hardlink => Ok(()),
+        LinkKind::Reflink => copy_metadata(&metadata, &temp_path),
+    }
+    .and_then(|()| Ok(fs::rename(&temp_path, duplicate)?));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+/// Create a link to `original` next to `duplicate`, under a name that isn't already taken.
+/// Returns the link's path.
+fn create_link(kind: LinkKind, original: &Path, duplicate: &Path) -> anyhow::Result<PathBuf> {
+    const MAX_ATTEMPTS: u32 = 100;
+
+    let Some(file_name) = duplicate.file_name() else {
+        bail!("it has no file name");
+    };
+
+    for attempt in 0..MAX_ATTEMPTS {
+        let mut temp_name = file_name.to_owned();
+        temp_name.push(format!(".dircs-link-{}-{attempt}", std::process::id()));
+        let temp_path = duplicate.with_file_name(temp_name);
+
+        // Neither of these replace an existing file.
+        let result = match kind {
+            LinkKind::Hardlink => fs::hard_link(original, &temp_path),
+            LinkKind::Reflink => reflink_copy::reflink(original, &temp_path),
+        };
+
+        match result {
+            Ok(()) => return Ok(temp_path),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    bail!("couldn't find an unused name for a temporary link")
+}
+
+/// Compare two files byte for byte.
+fn same_contents(a: &Path, b: &Path) -> anyhow::Result<bool> {
+    const BUFFER_SIZE: u64 = 64 * 1024;
+
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let (mut buffer_a, mut buffer_b) = (Vec::new(), Vec::new());
+    loop {
+        buffer_a.clear();
+        buffer_b.clear();
+        a.by_ref().take(BUFFER_SIZE).read_to_end(&mut buffer_a)?;
+        b.by_ref().take(BUFFER_SIZE).read_to_end(&mut buffer_b)?;
+
+        if buffer_a != buffer_b {
+            return Ok(false);
+        } else if buffer_a.is_empty() {
+            return Ok(true);
+        }
+    }
+}
+
+/// Give a newly made reflink the permissions, owner, and timestamps of the file it replaces.
+fn copy_metadata(metadata: &Metadata, path: &Path) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        std::os::unix::fs::chown(path, Some(metadata.uid()), Some(metadata.gid()))?;
+    }
+
+    fs::set_permissions(path, metadata.permissions())?;
+    filetime::set_file_times(
+        path,
+        filetime::FileTime::from_last_access_time(metadata),
+        filetime::FileTime::from_last_modification_time(metadata),
+    )?;
+
+    Ok(())
+}
+
+fn find_duplicates(args: &Args, paths: &[PathBuf]) -> anyhow::Result<Report> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    let mut seen_files = HashSet::new();
+
+    for path in paths {
+        for entry in build_walker(args, path)? {
+            let entry = match entry {
+                Ok(entry) if entry.file_type().is_file() => entry,
+                Ok(_) => continue,
+                Err(err) => {
+                    if args.verbose {
+                        println!("Found an issue with entry {err:?}, skipping.");
+                    }
+                    continue;
+                }
+            };
+
+            let path = entry.path();
+            let Ok(metadata) = path.metadata() else {
+                if args.verbose {
+                    println!("{} no longer exists, skipping", path.to_string_lossy());
+                }
+                continue;
+            };
+
+            // Empty files don't waste any space, and hardlinks to a file that was already
+            // found aren't copies of it.
+            if metadata.len() == 0
+                || file_id(&metadata).is_some_and(|file_id| !seen_files.insert(file_id))
+            {
+                continue;
+            }
+
+            by_size.entry(metadata.len()).or_default().push(path);
+        }
+    }
+
+    let candidates = by_size
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map(|(size, paths)| paths.into_iter().map(move |path| (size, path)))
+        .collect::<Vec<_>>();
+
+    let hash_function = args.hash_function();
+    let hashed = candidates
+        .into_par_iter()
+        .filter_map(|(size, path)| {
+            let result = File::open(&path).map_err(Into::into).and_then(|file| {
+                DircsHasher::new(hash_function).hash_target(TargetType::new(file, args.memmap))
+            });
+
+            match result {
+                Ok((hash, bytes_read)) => {
+                    if args.verbose {
+                        let hex = hex::encode(&hash);
+                        println!("{path:?} -> {hex} ({bytes_read} bytes read)");
+                    }
+                    Some((size, hash, path))
+                }
+                Err(err) => {
+                    if args.verbose {
+                        println!("Couldn't process {path:?} because: `{err:?}`. Skipping.");
+                    }
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut by_hash: HashMap<(u64, Vec<u8>), Vec<PathBuf>> = HashMap::new();
+    for (size, hash, path) in hashed {
+        by_hash.entry((size, hash)).or_default().push(path);
+    }
+
+    let mut sets = by_hash
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, hash), mut paths)| {
+            paths.sort();
+
+            DuplicateSet {
+                size,
+                hash: hex::encode(hash),
+                paths,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Show the sets that waste the most space first.
+    sets.sort_by(|a, b| {
+        b.wasted_bytes()
+            .cmp(&a.wasted_bytes())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+
+    Ok(Report {
+        duplicate_files: sets.iter().map(|set| set.paths.len() as u64 - 1).sum(),
+        wasted_bytes: sets.iter().map(DuplicateSet::wasted_bytes).sum(),
+        sets,
+    })
+}
+
+fn serialize_paths<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(paths.iter().map(|path| path.to_string_lossy()))
+}
+
+/// Whether two files have the same permissions and owner, so that one can be replaced with
+/// a hardlink to the other.
+#[cfg(unix)]
+fn same_owner(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    a.mode() == b.mode() && a.uid() == b.uid() && a.gid() == b.gid()
+}
+
+/// Whether two files have the same permissions and owner, so that one can be replaced with
+/// a hardlink to the other.
+#[cfg(not(unix))]
+fn same_owner(a: &Metadata, b: &Metadata) -> bool {
+    a.permissions() == b.permissions()
+}
+
+/// The device and inode of a file, which are the same for all hardlinks to it.
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// The device and inode of a file, which are the same for all hardlinks to it.
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
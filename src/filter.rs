@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+/// Filters entries during traversal based on user-provided include and exclude globs.
+///
+/// Globs are matched against the path relative to the traversal root. A `*` will not
+/// cross a path separator, so use `**` to match across directories.
+pub(crate) struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    /// Create a new filter. Returns `None` if there are no globs to filter with.
+    pub(crate) fn new(include: &[String], exclude: &[String]) -> anyhow::Result<Option<Self>> {
+        if include.is_empty() && exclude.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            include: build_glob_set(include)?,
+            exclude: build_glob_set(exclude)?,
+        }))
+    }
+
+    /// Whether an entry should be kept. Excluded directories are not descended into,
+    /// while include globs only apply to files, as otherwise a glob like `src/**/*.rs`
+    /// would never let us into `src` in the first place.
+    pub(crate) fn is_allowed(&self, relative_path: &Path, is_dir: bool) -> bool {
+        if let Some(exclude) = &self.exclude {
+            if exclude.is_match(relative_path) {
+                return false;
+            }
+        }
+
+        if is_dir {
+            return true;
+        }
+
+        match &self.include {
+            Some(include) => include.is_match(relative_path),
+            None => true,
+        }
+    }
+}
+
+fn build_glob_set(globs: &[String]) -> anyhow::Result<Option<GlobSet>> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        // Trailing slashes are trimmed so `target/` behaves like `target`.
+        builder.add(
+            GlobBuilder::new(glob.trim_end_matches('/'))
+                .literal_separator(true)
+                .build()?,
+        );
+    }
+
+    Ok(Some(builder.build()?))
+}
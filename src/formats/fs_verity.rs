@@ -0,0 +1,205 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::{ParallelBridge, ParallelIterator};
+
+use crate::{
+    TargetType,
+    args::Args,
+    hashers::{DircsHasher, HashFunction},
+    walk::build_walker,
+};
+
+/// fs-verity's default (and most widely supported) Merkle tree block size.
+const BLOCK_SIZE: usize = 4096;
+const LOG_BLOCK_SIZE: u8 = 12;
+
+/// The size of the root hash field in the descriptor, which fits the largest digest.
+const MAX_DIGEST_SIZE: usize = 64;
+
+/// Get the fs-verity digest of every file in a path, as output by `fsverity digest`.
+pub(crate) fn get_hashes(args: &Args, path: &Path) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let hash_function = args.hash_function();
+    let walker = build_walker(args, path)?;
+
+    let mut file_hashes = walker
+        .into_iter()
+        .enumerate()
+        .par_bridge()
+        .filter_map(|(index, entry)| {
+            let entry = entry.ok()?;
+            let entry_path = entry.path();
+
+            if entry_path.is_dir() {
+                return None;
+            }
+
+            let Ok(file) = File::open(&entry_path) else {
+                if args.verbose {
+                    println!(
+                        "{} cannot be opened, skipping",
+                        entry_path.to_string_lossy()
+                    );
+                }
+                return None;
+            };
+
+            let mut hasher = FsVerityHasher::new(hash_function);
+            match hasher.update_target(TargetType::new(file, args.memmap)) {
+                Ok(bytes_read) => {
+                    let hash = hasher.finalize();
+
+                    if args.verbose {
+                        let hex = hex::encode(&hash);
+                        println!("{entry_path:?} -> {hex} ({bytes_read} bytes read)");
+                    }
+
+                    Some((index, entry_path, hash))
+                }
+                Err(err) => {
+                    if args.verbose {
+                        println!("Couldn't process {entry_path:?} because: `{err:?}`. Skipping.");
+                    }
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    file_hashes.sort_by_key(|(index, _, _)| *index);
+
+    Ok(file_hashes
+        .into_iter()
+        .map(|(_, path, hash)| (path, hash))
+        .collect())
+}
+
+/// The fs-verity algorithm number for a hash function, if it's supported.
+pub(crate) fn algorithm_number(hash_function: HashFunction) -> Option<u8> {
+    match hash_function {
+        HashFunction::Sha2_256 => Some(1),
+        HashFunction::Sha2_512 => Some(2),
+        _ => None,
+    }
+}
+
+/// The name `fsverity digest` uses for a hash function, if it's supported.
+pub(crate) fn algorithm_name(hash_function: HashFunction) -> Option<&'static str> {
+    match hash_function {
+        HashFunction::Sha2_256 => Some("sha256"),
+        HashFunction::Sha2_512 => Some("sha512"),
+        _ => None,
+    }
+}
+
+/// Computes an fs-verity file digest, by building the file's Merkle tree one level at
+/// a time as data comes in and then hashing the fs-verity descriptor.
+struct FsVerityHasher {
+    hash_function: HashFunction,
+    data_size: u64,
+    /// The current, partially filled data block.
+    block: Vec<u8>,
+    /// The partially filled block of hashes at each level of the tree, along with how
+    /// many hashes have been added to that level in total.
+    levels: Vec<(Vec<u8>, u64)>,
+}
+
+impl FsVerityHasher {
+    fn new(hash_function: HashFunction) -> Self {
+        Self {
+            hash_function,
+            data_size: 0,
+            block: Vec::with_capacity(BLOCK_SIZE),
+            levels: Vec::new(),
+        }
+    }
+
+    fn update_target(&mut self, target: TargetType) -> anyhow::Result<usize> {
+        Ok(target.read_chunks(|bytes| self.update(bytes))?)
+    }
+
+    fn update(&mut self, mut bytes: &[u8]) {
+        self.data_size += bytes.len() as u64;
+
+        while !bytes.is_empty() {
+            let length = bytes.len().min(BLOCK_SIZE - self.block.len());
+            self.block.extend_from_slice(&bytes[..length]);
+            bytes = &bytes[length..];
+
+            if self.block.len() == BLOCK_SIZE {
+                let hash = self.hash_block(&self.block);
+                self.block.clear();
+                self.push_hash(0, hash);
+            }
+        }
+    }
+
+    /// Add a hash to a level, hashing the level's block into the next level once full.
+    fn push_hash(&mut self, level: usize, hash: Vec<u8>) {
+        if self.levels.len() == level {
+            self.levels.push((Vec::with_capacity(BLOCK_SIZE), 0));
+        }
+
+        let (block, count) = &mut self.levels[level];
+        block.extend_from_slice(&hash);
+        *count += 1;
+
+        if block.len() == BLOCK_SIZE {
+            let block = std::mem::take(block);
+            let hash = self.hash_block(&block);
+            self.push_hash(level + 1, hash);
+        }
+    }
+
+    fn finalize(mut self) -> Vec<u8> {
+        if !self.block.is_empty() {
+            let block = std::mem::take(&mut self.block);
+            let hash = self.hash_block(&block);
+            self.push_hash(0, hash);
+        }
+
+        // Empty files have an all-zero root hash.
+        let mut root_hash = Vec::new();
+        let mut level = 0;
+
+        while level < self.levels.len() {
+            let (block, count) = std::mem::take(&mut self.levels[level]);
+
+            // The top of the tree is the level with a single hash.
+            if count == 1 && level == self.levels.len() - 1 {
+                root_hash = block;
+                break;
+            }
+
+            if !block.is_empty() {
+                let hash = self.hash_block(&block);
+                self.push_hash(level + 1, hash);
+            }
+
+            level += 1;
+        }
+
+        let mut descriptor = Vec::with_capacity(256);
+        descriptor.push(1); // Version.
+        descriptor.push(algorithm_number(self.hash_function).unwrap_or_default());
+        descriptor.push(LOG_BLOCK_SIZE);
+        descriptor.push(0); // Salt size.
+        descriptor.extend_from_slice(&[0; 4]); // Reserved.
+        descriptor.extend_from_slice(&self.data_size.to_le_bytes());
+        root_hash.resize(MAX_DIGEST_SIZE, 0);
+        descriptor.extend_from_slice(&root_hash);
+        descriptor.resize(256, 0); // Salt and reserved space.
+
+        DircsHasher::new(self.hash_function).hash_bytes(&descriptor)
+    }
+
+    /// Hash a block, padding it with zeros if it isn't full.
+    fn hash_block(&self, block: &[u8]) -> Vec<u8> {
+        let mut hasher = DircsHasher::new(self.hash_function);
+        hasher.update(block);
+        hasher.update(&[0; BLOCK_SIZE][block.len()..]);
+        hasher.finalize()
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    TargetType,
+    args::Args,
+    hashers::{DircsHasher, HashFunction},
+    walk::build_walker,
+};
+
+const FILE_MODE: &str = "100644";
+const EXECUTABLE_MODE: &str = "100755";
+const SYMLINK_MODE: &str = "120000";
+const TREE_MODE: &str = "40000";
+
+pub(super) fn supports_hash_function(hash_function: HashFunction) -> bool {
+    match hash_function {
+        HashFunction::Sha1 => true,
+        #[cfg(feature = "sha2")]
+        HashFunction::Sha2_256 => true,
+        _ => false,
+    }
+}
+
+/// A file that will become a blob in a git tree.
+struct BlobEntry {
+    path: PathBuf,
+    components: Vec<OsString>,
+    mode: &'static str,
+}
+
+#[derive(Default)]
+struct TreeNode {
+    entries: BTreeMap<OsString, TreeEntry>,
+}
+
+enum TreeEntry {
+    Blob { mode: &'static str, id: Vec<u8> },
+    Tree(TreeNode),
+}
+
+/// Get the git object ID of a path - a blob ID if it is a file, or a tree ID if it
+/// is a directory. Like git, empty directories are ignored.
+pub(crate) fn get_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
+    let hash_function = args.hash_function();
+    let walker = build_walker(args, path)?;
+
+    let mut blob_entries = Vec::new();
+    for entry in walker {
+        let entry = entry?;
+        let entry_path = entry.path();
+        let file_type = entry.file_type();
+
+        let mode = if file_type.is_symlink() {
+            SYMLINK_MODE
+        } else if file_type.is_file() {
+            if super::is_executable(&entry.metadata()?) {
+                EXECUTABLE_MODE
+            } else {
+                FILE_MODE
+            }
+        } else {
+            continue;
+        };
+
+        // The root itself is a file, so we just want its blob ID.
+        if entry.depth == 0 {
+            return hash_blob(hash_function, &entry_path, mode, args.memmap);
+        }
+
+        let components = entry_path
+            .strip_prefix(path)?
+            .iter()
+            .map(|component| component.to_os_string())
+            .collect();
+
+        blob_entries.push(BlobEntry {
+            path: entry_path,
+            components,
+            mode,
+        });
+    }
+
+    let blobs = blob_entries
+        .into_par_iter()
+        .map(|blob_entry| {
+            let id = hash_blob(
+                hash_function,
+                &blob_entry.path,
+                blob_entry.mode,
+                args.memmap,
+            )?;
+
+            if args.verbose {
+                let hex = hex::encode(&id);
+                println!("{:?} -> {hex}", blob_entry.path);
+            }
+
+            Ok((blob_entry, id))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut root = TreeNode::default();
+    for (blob_entry, id) in blobs {
+        let Some((name, parents)) = blob_entry.components.split_last() else {
+            continue;
+        };
+
+        let mut node = &mut root;
+        for parent in parents {
+            let entry = node
+                .entries
+                .entry(parent.clone())
+                .or_insert_with(|| TreeEntry::Tree(TreeNode::default()));
+
+            node = match entry {
+                TreeEntry::Tree(tree) => tree,
+                TreeEntry::Blob { .. } => bail!("{parent:?} is both a file and a directory"),
+            };
+        }
+
+        node.entries.insert(
+            name.clone(),
+            TreeEntry::Blob {
+                mode: blob_entry.mode,
+                id,
+            },
+        );
+    }
+
+    Ok(hash_tree(hash_function, &root).unwrap_or_else(|| hash_object(hash_function, "tree", &[])))
+}
+
+/// Hash a tree. Returns `None` if the tree is empty, as git does not store those.
+fn hash_tree(hash_function: HashFunction, tree: &TreeNode) -> Option<Vec<u8>> {
+    let mut entries =
+        tree.entries
+            .iter()
+            .filter_map(|(name, entry)| match entry {
+                TreeEntry::Blob { mode, id } => Some((name.as_encoded_bytes(), *mode, id.clone())),
+                TreeEntry::Tree(subtree) => hash_tree(hash_function, subtree)
+                    .map(|id| (name.as_encoded_bytes(), TREE_MODE, id)),
+            })
+            .collect::<Vec<_>>();
+
+    if entries.is_empty() {
+        return None;
+    }
+
+    // Git sorts trees as if their names had a trailing slash.
+    entries.sort_by(|(a, a_mode, _), (b, b_mode, _)| {
+        let a = a.iter().chain((*a_mode == TREE_MODE).then_some(&b'/'));
+        let b = b.iter().chain((*b_mode == TREE_MODE).then_some(&b'/'));
+        a.cmp(b)
+    });
+
+    let mut contents = Vec::new();
+    for (name, mode, id) in entries {
+        contents.extend_from_slice(mode.as_bytes());
+        contents.push(b' ');
+        contents.extend_from_slice(name);
+        contents.push(0);
+        contents.extend_from_slice(&id);
+    }
+
+    Some(hash_object(hash_function, "tree", &contents))
+}
+
+fn hash_object(hash_function: HashFunction, kind: &str, contents: &[u8]) -> Vec<u8> {
+    let mut hasher = DircsHasher::new(hash_function);
+    hasher.update(format!("{kind} {}\0", contents.len()).as_bytes());
+    hasher.hash_bytes(contents)
+}
+
+fn hash_blob(
+    hash_function: HashFunction,
+    path: &Path,
+    mode: &str,
+    memmap: bool,
+) -> anyhow::Result<Vec<u8>> {
+    if mode == SYMLINK_MODE {
+        // Git stores the symlink's target as the blob's contents.
+        let target = fs::read_link(path)?;
+        return Ok(hash_object(
+            hash_function,
+            "blob",
+            target.as_os_str().as_encoded_bytes(),
+        ));
+    }
+
+    let file = File::open(path)?;
+    let expected_len = file.metadata()?.len();
+
+    let mut hasher = DircsHasher::new(hash_function);
+    hasher.update(format!("blob {expected_len}\0").as_bytes());
+
+    let (id, bytes_read) = hasher.hash_target(TargetType::new(file, memmap))?;
+    if bytes_read as u64 != expected_len {
+        bail!("{} changed while it was being read", path.to_string_lossy());
+    }
+
+    Ok(id)
+}
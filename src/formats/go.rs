@@ -0,0 +1,84 @@
+use std::{fs::File, path::Path};
+
+use anyhow::bail;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{TargetType, args::Args, hashers::DircsHasher, walk::build_walker};
+
+/// Get Go's `h1:` hash of a path, as computed by `dirhash.HashDir` and stored in `go.sum`.
+///
+/// This is the SHA-256 of a summary containing a `<sha256>  <name>` line for every file,
+/// sorted by name. Names are relative to the directory and joined to the prefix (usually
+/// `module@version`) if there is one. If the path is a file, its name is just its file name,
+/// which is how the `/go.mod` hashes in `go.sum` are computed.
+pub(crate) fn get_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
+    let hash_function = args.hash_function();
+    let prefix = args
+        .go_prefix
+        .as_deref()
+        .map(|prefix| prefix.trim_end_matches('/'))
+        .filter(|prefix| !prefix.is_empty());
+
+    let walker = build_walker(args, path)?;
+
+    let mut files = Vec::new();
+    for entry in walker {
+        let entry = entry?;
+
+        if entry.file_type().is_dir() {
+            continue;
+        }
+
+        let entry_path = entry.path();
+        let relative_path = if entry.depth == 0 {
+            Path::new(&entry.file_name)
+        } else {
+            entry_path.strip_prefix(path)?
+        };
+
+        let mut name = prefix
+            .map(|prefix| prefix.as_bytes().to_vec())
+            .unwrap_or_default();
+        for component in relative_path {
+            if !name.is_empty() {
+                name.push(b'/');
+            }
+
+            name.extend_from_slice(component.as_encoded_bytes());
+        }
+
+        if name.contains(&b'\n') {
+            bail!("file names with newlines are not supported: {entry_path:?}");
+        }
+
+        files.push((name, entry_path));
+    }
+
+    let mut file_hashes = files
+        .into_par_iter()
+        .map(|(name, file_path)| {
+            let file = File::open(&file_path)?;
+            let (hash, bytes_read) =
+                DircsHasher::new(hash_function).hash_target(TargetType::new(file, args.memmap))?;
+
+            if args.verbose {
+                let hex = hex::encode(&hash);
+                println!("{file_path:?} -> {hex} ({bytes_read} bytes read)");
+            }
+
+            Ok((name, hash))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    file_hashes.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut summary = DircsHasher::new(hash_function);
+    for (name, hash) in file_hashes {
+        summary.update(hex::encode(hash).as_bytes());
+        summary.update(b"  ");
+        summary.update(&name);
+        summary.update(b"\n");
+    }
+
+    Ok(summary.finalize())
+}
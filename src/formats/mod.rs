@@ -0,0 +1,125 @@
+#[cfg(feature = "sha2")]
+pub(crate) mod fs_verity;
+#[cfg(feature = "sha1")]
+pub(crate) mod git;
+#[cfg(feature = "sha2")]
+pub(crate) mod go;
+pub(crate) mod nar;
+pub(crate) mod structure;
+
+use std::fs::Metadata;
+
+use anyhow::bail;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use clap::ValueEnum;
+
+use crate::hashers::HashFunction;
+
+/// The various output formats. Formats other than the default one generally aim to be
+/// compatible with some other tool.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+    /// dircs' own format, which combines the hashes of each file.
+    #[default]
+    Dircs,
+    /// Git object IDs - a blob ID for a file, or a tree ID for a directory. Uses SHA-1 by
+    /// default, or SHA-256 with `-f sha2-256`. `.git` directories are always skipped.
+    #[cfg(feature = "sha1")]
+    Git,
+    /// The hash of a path's Nix archive (NAR) serialization, the same as `nix hash path`.
+    /// Uses SHA-256 by default.
+    Nar,
+    /// Go module hashes (`h1:`), as stored in `go.sum`. Use `--go-prefix` to set the
+    /// module path and version prefix.
+    #[cfg(feature = "sha2")]
+    Go,
+    /// fs-verity file digests, the same as `fsverity digest`. Unlike other formats, this
+    /// outputs a digest for every file. Uses SHA-256 by default, or SHA-512 with
+    /// `-f sha2-512`.
+    #[cfg(feature = "sha2")]
+    FsVerity,
+    /// A fingerprint of the path's structure - the names, types, and sizes of every entry -
+    /// without reading any files.
+    Structure,
+}
+
+impl Format {
+    /// The hash function to use if one isn't specified.
+    pub(crate) fn default_hash_function(self) -> HashFunction {
+        match self {
+            Format::Dircs => HashFunction::Blake3,
+            #[cfg(feature = "sha1")]
+            Format::Git => HashFunction::Sha1,
+            #[cfg(feature = "sha2")]
+            Format::Nar => HashFunction::Sha2_256,
+            #[cfg(not(feature = "sha2"))]
+            Format::Nar => HashFunction::Blake3,
+            #[cfg(feature = "sha2")]
+            Format::Go => HashFunction::Sha2_256,
+            #[cfg(feature = "sha2")]
+            Format::FsVerity => HashFunction::Sha2_256,
+            Format::Structure => HashFunction::Blake3,
+        }
+    }
+
+    /// Check whether a hash function can be used with this format.
+    pub(crate) fn check_hash_function(self, hash_function: HashFunction) -> anyhow::Result<()> {
+        let is_supported = match self {
+            Format::Dircs | Format::Structure => true,
+            #[cfg(feature = "sha1")]
+            Format::Git => git::supports_hash_function(hash_function),
+            Format::Nar => nar::sri_name(hash_function).is_some(),
+            #[cfg(feature = "sha2")]
+            Format::Go => matches!(hash_function, HashFunction::Sha2_256),
+            #[cfg(feature = "sha2")]
+            Format::FsVerity => fs_verity::algorithm_number(hash_function).is_some(),
+        };
+
+        if !is_supported {
+            bail!(
+                "the {} format does not support {}",
+                value_name(self),
+                value_name(hash_function)
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Encode a hash into the string that should be output for this format.
+    pub(crate) fn encode(self, hash_function: HashFunction, hash: &[u8]) -> String {
+        match self {
+            Format::Nar => match nar::sri_name(hash_function) {
+                Some(name) => format!("{name}-{}", BASE64_STANDARD.encode(hash)),
+                None => hex::encode(hash),
+            },
+            #[cfg(feature = "sha2")]
+            Format::Go => format!("h1:{}", BASE64_STANDARD.encode(hash)),
+            #[cfg(feature = "sha2")]
+            Format::FsVerity => match fs_verity::algorithm_name(hash_function) {
+                Some(name) => format!("{name}:{}", hex::encode(hash)),
+                None => hex::encode(hash),
+            },
+            _ => hex::encode(hash),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o100 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &Metadata) -> bool {
+    false
+}
+
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
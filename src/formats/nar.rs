@@ -0,0 +1,162 @@
+use std::{
+    fs::{self, File},
+    path::Path,
+};
+
+use anyhow::bail;
+
+use crate::{
+    TargetType,
+    args::Args,
+    hashers::{DircsHasher, HashFunction},
+    walk::build_walker,
+};
+
+/// Returns the name used for a hash function in an [SRI](https://www.w3.org/TR/SRI/) hash,
+/// which is what `nix hash path` outputs.
+pub(super) fn sri_name(hash_function: HashFunction) -> Option<&'static str> {
+    match hash_function {
+        #[cfg(feature = "md5")]
+        HashFunction::MD5 => Some("md5"),
+        #[cfg(feature = "sha1")]
+        HashFunction::Sha1 => Some("sha1"),
+        #[cfg(feature = "sha2")]
+        HashFunction::Sha2_256 => Some("sha256"),
+        #[cfg(feature = "sha2")]
+        HashFunction::Sha2_512 => Some("sha512"),
+        _ => None,
+    }
+}
+
+/// Writes Nix archive (NAR) serialization directly into a hasher.
+struct NarHasher {
+    hasher: DircsHasher,
+}
+
+impl NarHasher {
+    /// Write a string, which is its length, the bytes, then padding to a multiple of 8 bytes.
+    fn write_str(&mut self, bytes: &[u8]) {
+        self.hasher.update(&(bytes.len() as u64).to_le_bytes());
+        self.hasher.update(bytes);
+        self.write_padding(bytes.len() as u64);
+    }
+
+    fn write_padding(&mut self, len: u64) {
+        let padding = (8 - len % 8) % 8;
+        self.hasher.update(&[0; 8][..padding as usize]);
+    }
+
+    fn write_file(&mut self, path: &Path, memmap: bool) -> anyhow::Result<()> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let expected_len = metadata.len();
+
+        self.write_str(b"type");
+        self.write_str(b"regular");
+
+        if super::is_executable(&metadata) {
+            self.write_str(b"executable");
+            self.write_str(b"");
+        }
+
+        self.write_str(b"contents");
+        self.hasher.update(&expected_len.to_le_bytes());
+
+        let bytes_read = self.hasher.update_target(TargetType::new(file, memmap))? as u64;
+        if bytes_read != expected_len {
+            bail!("{} changed while it was being read", path.to_string_lossy());
+        }
+
+        self.write_padding(bytes_read);
+
+        Ok(())
+    }
+
+    fn write_symlink(&mut self, path: &Path) -> anyhow::Result<()> {
+        let target = fs::read_link(path)?;
+
+        self.write_str(b"type");
+        self.write_str(b"symlink");
+        self.write_str(b"target");
+        self.write_str(target.as_os_str().as_encoded_bytes());
+
+        Ok(())
+    }
+}
+
+/// Get the hash of a path's NAR serialization, the same as `nix hash path`.
+///
+/// Entries are visited in the order that traversal returns them - depth-first, sorted
+/// by name - which is the same order that a NAR stores them in.
+pub(crate) fn get_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
+    let walker = build_walker(args, path)?;
+    let mut nar = NarHasher {
+        hasher: DircsHasher::new(args.hash_function()),
+    };
+
+    nar.write_str(b"nix-archive-1");
+
+    // The depths of the directories we are currently in.
+    let mut open_dirs: Vec<usize> = Vec::new();
+
+    // Closes a node, as well as the entry wrapping it if it isn't the root.
+    let close_node = |nar: &mut NarHasher, depth: usize| {
+        nar.write_str(b")");
+
+        if depth > 0 {
+            nar.write_str(b")");
+        }
+    };
+
+    for entry in walker {
+        let entry = entry?;
+        let entry_path = entry.path();
+        let file_type = entry.file_type();
+
+        while open_dirs.last().is_some_and(|depth| *depth >= entry.depth) {
+            let depth = open_dirs.pop().unwrap();
+            close_node(&mut nar, depth);
+        }
+
+        if !(file_type.is_dir() || file_type.is_file() || file_type.is_symlink()) {
+            if args.verbose {
+                println!("{entry_path:?} is not a file, directory, or symlink, skipping");
+            }
+            continue;
+        }
+
+        if entry.depth > 0 {
+            nar.write_str(b"entry");
+            nar.write_str(b"(");
+            nar.write_str(b"name");
+            nar.write_str(entry.file_name.as_encoded_bytes());
+            nar.write_str(b"node");
+        }
+
+        nar.write_str(b"(");
+
+        if file_type.is_dir() {
+            nar.write_str(b"type");
+            nar.write_str(b"directory");
+            open_dirs.push(entry.depth);
+
+            continue;
+        } else if file_type.is_symlink() {
+            nar.write_symlink(&entry_path)?;
+        } else {
+            nar.write_file(&entry_path, args.memmap)?;
+
+            if args.verbose {
+                println!("{entry_path:?} added to the archive");
+            }
+        }
+
+        close_node(&mut nar, entry.depth);
+    }
+
+    while let Some(depth) = open_dirs.pop() {
+        close_node(&mut nar, depth);
+    }
+
+    Ok(nar.hasher.finalize())
+}
@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use crate::{args::Args, hashers::DircsHasher, walk::build_walker};
+
+/// Get a fingerprint of a path's structure, without reading any files.
+///
+/// For every entry in traversal order, this hashes a `<type> <size> <path>` record
+/// terminated by a NUL byte. The type is `d` for directories, `f` for files, `l` for
+/// symlinks, and `o` for anything else. Paths are relative to the path being hashed,
+/// and sizes are zero for directories since they depend on the filesystem. If the path
+/// is a file, its record just uses its file name.
+pub(crate) fn get_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
+    let mut hasher = DircsHasher::new(args.hash_function());
+
+    for entry in build_walker(args, path)? {
+        let entry = entry?;
+        let file_type = entry.file_type();
+
+        // The root directory itself isn't part of its structure.
+        if entry.depth == 0 && file_type.is_dir() {
+            continue;
+        }
+
+        let (kind, size) = if file_type.is_dir() {
+            ('d', 0)
+        } else if file_type.is_file() {
+            ('f', entry.metadata()?.len())
+        } else if file_type.is_symlink() {
+            ('l', entry.metadata()?.len())
+        } else {
+            ('o', 0)
+        };
+
+        let entry_path = entry.path();
+        let relative_path = if entry.depth == 0 {
+            Path::new(&entry.file_name)
+        } else {
+            entry_path.strip_prefix(path)?
+        };
+
+        if args.verbose {
+            println!("{kind} {size} {}", relative_path.to_string_lossy());
+        }
+
+        hasher.update(format!("{kind} {size} ").as_bytes());
+
+        let mut first = true;
+        for component in relative_path {
+            if !first {
+                hasher.update(b"/");
+            }
+
+            hasher.update(component.as_encoded_bytes());
+            first = false;
+        }
+
+        hasher.update(b"\0");
+    }
+
+    Ok(hasher.finalize())
+}
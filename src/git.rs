@@ -0,0 +1,236 @@
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow, bail};
+use git2::{ObjectType, Oid, Repository, Tree};
+use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    args::Args, bytes_to_path, combine_file_hashes, filter::PathFilter, hashers::DircsHasher,
+};
+
+/// Git's file mode for submodules, which are stored as commits rather than files.
+const SUBMODULE_MODE: u32 = 0o160000;
+
+/// The files tracked in a git repository's index, used to restrict traversal.
+pub(crate) struct TrackedFiles {
+    files: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl TrackedFiles {
+    /// Read the index of the repository that `path` is in. `path` should be canonical.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        let repo = Repository::discover(path)?;
+        let workdir = workdir(&repo)?;
+
+        let mut files = HashSet::new();
+        let mut dirs = HashSet::new();
+
+        for entry in repo.index()?.iter() {
+            if entry.mode == SUBMODULE_MODE {
+                continue;
+            }
+
+            let file = workdir.join(bytes_to_path(&entry.path)?);
+
+            for dir in file.ancestors().skip(1) {
+                if !dirs.insert(dir.to_path_buf()) || dir == workdir {
+                    break;
+                }
+            }
+
+            files.insert(file);
+        }
+
+        Ok(Self { files, dirs })
+    }
+
+    /// Whether a path is tracked, or is a directory containing tracked files.
+    pub(crate) fn contains(&self, path: &Path, is_dir: bool) -> bool {
+        if is_dir {
+            self.dirs.contains(path)
+        } else {
+            self.files.contains(path)
+        }
+    }
+}
+
+/// Get the hash of a path as it is in a specific revision of the git repository it
+/// is in, reading files directly from the repository's object database.
+///
+/// The traversal mirrors what would happen for a checkout of that revision, so the
+/// result is the same as hashing the path in a clean checkout.
+pub(crate) fn get_rev_hash(args: &Args, path: &Path, rev: &str) -> anyhow::Result<Vec<u8>> {
+    let (repo, relative_path) = resolve_in_repo(path)
+        .with_context(|| format!("could not resolve {}", path.to_string_lossy()))?;
+    let relative_path = relative_path.as_path();
+
+    let tree = repo
+        .revparse_single(rev)?
+        .peel_to_tree()
+        .with_context(|| format!("`{rev}` does not point to a tree"))?;
+
+    if args.verbose {
+        println!("Reading files from revision {}.", tree.id());
+    }
+
+    let mut blobs = Vec::new();
+
+    if relative_path.as_os_str().is_empty() {
+        collect_blobs(&repo, &tree, args, &mut blobs)?;
+    } else {
+        let entry = tree
+            .get_path(relative_path)
+            .with_context(|| format!("{} does not exist in `{rev}`", path.to_string_lossy()))?;
+
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                let subtree = repo.find_tree(entry.id())?;
+                collect_blobs(&repo, &subtree, args, &mut blobs)?;
+            }
+            Some(ObjectType::Blob) => blobs.push((relative_path.to_path_buf(), entry.id())),
+            _ => bail!("{} is not a file or directory", path.to_string_lossy()),
+        }
+    }
+
+    // Repositories can't be shared between threads, so each thread opens its own.
+    let repo_path = repo.path().to_path_buf();
+    let hasher = DircsHasher::new(args.hash_function());
+
+    let file_hash_results = blobs
+        .into_par_iter()
+        .enumerate()
+        .map_init(
+            || Repository::open(&repo_path),
+            |repo, (index, (blob_path, oid))| {
+                let repo = repo
+                    .as_ref()
+                    .map_err(|err| anyhow!(err.message().to_owned()))?;
+                let blob = repo.find_blob(oid)?;
+                let result = hasher.clone().hash_bytes(blob.content());
+
+                if args.verbose {
+                    let hex = hex::encode(&result);
+                    let size = blob.size();
+                    println!("{blob_path:?} -> {hex} ({size} bytes read)");
+                }
+
+                Ok((index, result))
+            },
+        )
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    combine_file_hashes(hasher, file_hash_results)
+}
+
+/// Collect all blobs in a tree in the same order that file traversal would, taking
+/// into account the traversal options.
+fn collect_blobs(
+    repo: &Repository,
+    tree: &Tree<'_>,
+    args: &Args,
+    blobs: &mut Vec<(PathBuf, Oid)>,
+) -> anyhow::Result<()> {
+    let filter = PathFilter::new(&args.include, &args.exclude)?;
+    collect_blobs_inner(
+        repo,
+        tree,
+        args,
+        filter.as_ref(),
+        &mut PathBuf::new(),
+        blobs,
+    )
+}
+
+fn collect_blobs_inner(
+    repo: &Repository,
+    tree: &Tree<'_>,
+    args: &Args,
+    filter: Option<&PathFilter>,
+    relative_path: &mut PathBuf,
+    blobs: &mut Vec<(PathBuf, Oid)>,
+) -> anyhow::Result<()> {
+    let depth = relative_path.components().count() + 1;
+    if args.depth.is_some_and(|max_depth| depth > max_depth) {
+        return Ok(());
+    }
+
+    // Git sorts directories as if they had a trailing slash, but traversal sorts by name.
+    let mut entries = tree.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.name_bytes().cmp(b.name_bytes()));
+
+    for entry in entries {
+        let name = entry.name_bytes();
+
+        if args.skip_hidden && name.starts_with(b".") {
+            continue;
+        }
+
+        relative_path.push(bytes_to_path(name)?);
+
+        let is_allowed =
+            |is_dir| filter.is_none_or(|filter| filter.is_allowed(relative_path, is_dir));
+
+        match entry.kind() {
+            Some(ObjectType::Tree) if is_allowed(true) => {
+                let subtree = repo.find_tree(entry.id())?;
+                collect_blobs_inner(repo, &subtree, args, filter, relative_path, blobs)?;
+            }
+            Some(ObjectType::Blob) if is_allowed(false) => {
+                blobs.push((relative_path.clone(), entry.id()));
+            }
+            // Submodules show up as commits, which we skip along with anything filtered out.
+            _ => {}
+        }
+
+        relative_path.pop();
+    }
+
+    Ok(())
+}
+
+/// Find the repository a path is in, and the path relative to its working directory.
+///
+/// The path doesn't need to exist, as it might only be in another revision, so it's
+/// resolved lexically. Only the part of it that exists is canonicalized, so that it
+/// matches the working directory if either is behind a symlink.
+fn resolve_in_repo(path: &Path) -> anyhow::Result<(Repository, PathBuf)> {
+    let mut absolute_path = PathBuf::new();
+    for component in std::path::absolute(path)?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute_path.pop();
+            }
+            component => absolute_path.push(component),
+        }
+    }
+
+    let existing = absolute_path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .context("no part of the path exists")?;
+    let repo = Repository::discover(existing)?;
+    let workdir = workdir(&repo)?;
+
+    let resolved_path = existing
+        .canonicalize()?
+        .join(absolute_path.strip_prefix(existing)?);
+    let relative_path = resolved_path
+        .strip_prefix(&workdir)
+        .context("the path is outside of the repository's working directory")?
+        .to_path_buf();
+
+    Ok((repo, relative_path))
+}
+
+fn workdir(repo: &Repository) -> anyhow::Result<PathBuf> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("bare repositories are not supported"))?;
+
+    Ok(workdir.canonicalize()?)
+}
@@ -1,3 +1,8 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
 use clap::ValueEnum;
 
 use crate::TargetType;
@@ -5,6 +10,9 @@ use crate::TargetType;
 // Limit is 128 KiB.
 const SKIP_RAYON_LIMIT: usize = 128 * 1024;
 
+/// The size of each block read when sampling a file.
+const SAMPLE_BLOCK_SIZE: u64 = 64 * 1024;
+
 /// The various hash functions.
 #[derive(Default, Clone, Copy, Debug, ValueEnum)]
 pub(crate) enum HashFunction {
@@ -169,48 +177,105 @@ impl DircsHasher {
         self.state.finalize()
     }
 
-    pub(crate) fn hash_target(
+    /// Update the hasher's state with some bytes.
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        self.state.update(bytes);
+    }
+
+    /// Hash bytes that are already in memory.
+    pub(crate) fn hash_bytes(mut self, bytes: &[u8]) -> Vec<u8> {
+        self.state.update(bytes);
+        self.state.finalize()
+    }
+
+    /// Finalize the hash computation and return a hash.
+    pub(crate) fn finalize(self) -> Vec<u8> {
+        self.state.finalize()
+    }
+
+    pub(crate) fn hash_target(mut self, target: TargetType) -> anyhow::Result<(Vec<u8>, usize)> {
+        let total_bytes = self.update_target(target)?;
+
+        Ok((self.state.finalize(), total_bytes))
+    }
+
+    /// Hash a target, along with each `piece_size` block of it. The last block may be
+    /// shorter. Returns the hash, the hashes of each block, and the number of bytes read.
+    pub(crate) fn hash_target_piecewise(
         mut self,
-        mut target: TargetType,
-    ) -> anyhow::Result<(Vec<u8>, usize)> {
-        match (&target, &mut self.state) {
-            (TargetType::MMap(cursor), InternalHasher::Blake3(hasher)) => {
-                // If we have memmap and blake3 enabled, we can use this nifty feature!
+        target: TargetType,
+        piece_size: u64,
+    ) -> anyhow::Result<(Vec<u8>, Vec<Vec<u8>>, usize)> {
+        let empty_hasher = self.clone();
+        let mut pieces = Vec::new();
+        let mut piece_hasher = empty_hasher.clone();
+        let mut piece_filled = 0;
 
-                let total_bytes = cursor.get_ref().len();
+        let total_bytes = target.read_chunks(|mut bytes| {
+            self.state.update(bytes);
 
-                if total_bytes >= SKIP_RAYON_LIMIT {
-                    hasher.update_rayon(cursor.get_ref());
-                } else {
-                    hasher.update(cursor.get_ref());
-                }
+            while !bytes.is_empty() {
+                let len = bytes.len().min((piece_size - piece_filled) as usize);
+                piece_hasher.update(&bytes[..len]);
+                piece_filled += len as u64;
+                bytes = &bytes[len..];
 
-                Ok((hasher.finalize().as_bytes().to_vec(), total_bytes))
-            }
-            _ => {
-                const BUFFER_SIZE: usize = 64 * 1024; // 64 KiB buffer size.
-                let mut buffer = [0; BUFFER_SIZE];
-                let mut total_bytes = 0;
-
-                loop {
-                    match target.read(&mut buffer) {
-                        Ok(0) => {
-                            return Ok((self.state.finalize(), total_bytes));
-                        }
-                        Ok(bytes_read) => {
-                            total_bytes += bytes_read;
-                            self.state.update(&buffer[..bytes_read]);
-                        }
-                        Err(err) => {
-                            if err.kind() == std::io::ErrorKind::Interrupted {
-                                continue;
-                            } else {
-                                return Err(err.into());
-                            }
-                        }
-                    }
+                if piece_filled == piece_size {
+                    pieces.push(
+                        std::mem::replace(&mut piece_hasher, empty_hasher.clone()).finalize(),
+                    );
+                    piece_filled = 0;
                 }
             }
+        })?;
+
+        if piece_filled > 0 {
+            pieces.push(piece_hasher.finalize());
+        }
+
+        Ok((self.state.finalize(), pieces, total_bytes))
+    }
+
+    /// Hash a sample of a file: its size, its first and last blocks, and `blocks` evenly
+    /// spaced blocks in between. Files too small to be worth sampling are hashed in full,
+    /// the same as with `hash_target`. Returns the hash and the number of bytes read.
+    pub(crate) fn hash_sampled(
+        mut self,
+        mut file: File,
+        blocks: u64,
+        memmap: bool,
+    ) -> anyhow::Result<(Vec<u8>, usize)> {
+        let size = file.metadata()?.len();
+        let sample_size = blocks.saturating_add(2).saturating_mul(SAMPLE_BLOCK_SIZE);
+
+        if size <= sample_size {
+            return self.hash_target(TargetType::new(file, memmap));
         }
+
+        self.update(&size.to_le_bytes());
+
+        let mut buffer = vec![0; SAMPLE_BLOCK_SIZE as usize];
+        let last_offset = size - SAMPLE_BLOCK_SIZE;
+
+        for index in 0..blocks + 2 {
+            let offset =
+                (u128::from(last_offset) * u128::from(index) / u128::from(blocks + 1)) as u64;
+
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut buffer)?;
+            self.update(&buffer);
+        }
+
+        Ok((
+            self.finalize(),
+            usize::try_from(sample_size).unwrap_or(usize::MAX),
+        ))
+    }
+
+    /// Update the hasher's state with the entire contents of a target, returning the
+    /// number of bytes read.
+    pub(crate) fn update_target(&mut self, target: TargetType) -> anyhow::Result<usize> {
+        // Memmapped targets are hashed in one go, which lets blake3 use multiple threads.
+        Ok(target.read_chunks(|bytes| self.state.update(bytes))?)
     }
 }
@@ -0,0 +1,162 @@
+use std::{fs::File, io::Read, path::Path, sync::Arc};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+/// A dircs-specific ignore file, using the same syntax as a `.gitignore`.
+const DIRCS_IGNORE_FILE: &str = ".dircsignore";
+
+/// See <https://bford.info/cachedir/>.
+const CACHEDIR_TAG_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// The ignore files found in a single directory.
+#[derive(Debug)]
+struct Level {
+    dircsignore: Option<Gitignore>,
+    ignore: Option<Gitignore>,
+    gitignore: Option<Gitignore>,
+    git_exclude: Option<Gitignore>,
+}
+
+impl Level {
+    fn load(dir: &Path) -> Option<Self> {
+        let level = Self {
+            dircsignore: load_ignore_file(dir, &dir.join(DIRCS_IGNORE_FILE)),
+            ignore: load_ignore_file(dir, &dir.join(".ignore")),
+            gitignore: load_ignore_file(dir, &dir.join(".gitignore")),
+            git_exclude: load_ignore_file(dir, &dir.join(".git").join("info").join("exclude")),
+        };
+
+        if level.dircsignore.is_none()
+            && level.ignore.is_none()
+            && level.gitignore.is_none()
+            && level.git_exclude.is_none()
+        {
+            None
+        } else {
+            Some(level)
+        }
+    }
+}
+
+fn load_ignore_file(dir: &Path, file: &Path) -> Option<Gitignore> {
+    if !file.is_file() {
+        return None;
+    }
+
+    let mut builder = GitignoreBuilder::new(dir);
+    builder.add(file);
+
+    builder
+        .build()
+        .ok()
+        .filter(|gitignore| !gitignore.is_empty())
+}
+
+/// The set of ignore rules that apply to a directory during traversal.
+///
+/// Rules are checked in the same order of precedence that ripgrep uses: `.dircsignore`
+/// files first, then `.ignore` files, then `.gitignore` files, then the repository's
+/// `.git/info/exclude`, and finally the global git excludes file. Within each of these,
+/// files in deeper directories take precedence.
+///
+/// All paths handed to these rules are expected to be canonical.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct IgnoreRules {
+    /// Levels, ordered from the outermost directory inwards.
+    levels: Vec<Arc<Level>>,
+    global: Option<Arc<Gitignore>>,
+}
+
+impl IgnoreRules {
+    /// Create the initial rules for a traversal root. This also loads ignore files
+    /// in the root's parent directories, up to the root of the git repository the
+    /// root is in (if any), as git would apply those too.
+    pub(crate) fn for_root(root: &Path) -> Self {
+        let mut levels = Vec::new();
+        let mut repo_root = None;
+
+        if root.join(".git").exists() {
+            repo_root = Some(root.to_path_buf());
+        } else {
+            for ancestor in root.ancestors().skip(1) {
+                if let Some(level) = Level::load(ancestor) {
+                    levels.push(Arc::new(level));
+                }
+
+                if ancestor.join(".git").exists() {
+                    repo_root = Some(ancestor.to_path_buf());
+                    break;
+                }
+            }
+
+            // Ancestors' ignore files only apply if we're actually in a repository.
+            if repo_root.is_none() {
+                levels.clear();
+            }
+        }
+
+        levels.reverse();
+
+        let global = repo_root.map(|repo_root| {
+            let (global, _) = GitignoreBuilder::new(repo_root).build_global();
+            Arc::new(global)
+        });
+
+        Self {
+            levels,
+            global: global.filter(|global| !global.is_empty()),
+        }
+    }
+
+    /// Returns the rules that apply to entries within `dir`.
+    pub(crate) fn with_dir(&self, dir: &Path) -> Self {
+        match Level::load(dir) {
+            Some(level) => {
+                let mut rules = self.clone();
+                rules.levels.push(Arc::new(level));
+                rules
+            }
+            None => self.clone(),
+        }
+    }
+
+    /// Whether a path should be ignored.
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        // Git never considers its own directory to be part of a project.
+        if is_dir && path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+
+        let matchers: [fn(&Level) -> Option<&Gitignore>; 4] = [
+            |level| level.dircsignore.as_ref(),
+            |level| level.ignore.as_ref(),
+            |level| level.gitignore.as_ref(),
+            |level| level.git_exclude.as_ref(),
+        ];
+
+        for matcher in matchers {
+            for level in self.levels.iter().rev() {
+                if let Some(gitignore) = matcher(level) {
+                    let result = gitignore.matched(path, is_dir);
+
+                    if !result.is_none() {
+                        return result.is_ignore();
+                    }
+                }
+            }
+        }
+
+        self.global
+            .as_ref()
+            .is_some_and(|global| global.matched(path, is_dir).is_ignore())
+    }
+}
+
+/// Whether a directory is marked as a cache directory with a valid `CACHEDIR.TAG` file.
+pub(crate) fn is_cache_dir(dir: &Path) -> bool {
+    let mut buffer = [0; CACHEDIR_TAG_SIGNATURE.len()];
+
+    File::open(dir.join("CACHEDIR.TAG"))
+        .and_then(|mut file| file.read_exact(&mut buffer))
+        .is_ok_and(|_| buffer == CACHEDIR_TAG_SIGNATURE)
+}
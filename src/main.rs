@@ -2,16 +2,33 @@
 //! the hash of a file or directory.
 
 mod args;
+mod bao;
+mod cache;
+mod check;
+mod chunks;
+mod cmp;
+mod dupes;
+mod filter;
+mod formats;
+#[cfg(feature = "git")]
+mod git;
 mod hashers;
+mod ignore_files;
+mod manifest;
 mod memmap;
+mod metadata;
+mod walk;
 
 #[cfg(feature = "progress")]
 mod progress;
+#[cfg(unix)]
+mod tag;
 
 use std::{
     fs::File,
-    io::{Cursor, Read},
-    path::Path,
+    io::{Read, Seek, SeekFrom, Take},
+    ops::Range,
+    path::{Path, PathBuf},
     time::Instant,
 };
 
@@ -21,70 +38,182 @@ use args::*;
 use progress::ProgressBarState;
 
 use anyhow::bail;
-use clap::Parser;
+use cache::HashCache;
+use clap::{CommandFactory, Parser};
+use formats::Format;
 use hashers::DircsHasher;
-use jwalk::WalkDir;
 use memmap::try_memmap;
 use rayon::{
     ThreadPoolBuilder,
     prelude::{ParallelBridge, ParallelIterator},
 };
+use walk::build_walker;
 
 enum TargetType {
-    MMap(Cursor<memmap2::Mmap>),
-    File(File),
+    MMap(memmap2::Mmap, Range<usize>),
+    File(Take<File>),
 }
 
 impl TargetType {
-    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
-        match self {
-            TargetType::MMap(cursor) => cursor.read(buffer),
-            TargetType::File(file) => file.read(buffer),
+    /// Wrap a file, memmapping it if enabled and possible.
+    fn new(file: File, memmap: bool) -> Self {
+        if memmap {
+            match try_memmap(&file) {
+                Ok(Some(mmap)) => {
+                    let len = mmap.len();
+                    TargetType::MMap(mmap, 0..len)
+                }
+                _ => TargetType::File(file.take(u64::MAX)),
+            }
+        } else {
+            TargetType::File(file.take(u64::MAX))
         }
     }
-}
 
-fn get_path_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
-    let mut walker = WalkDir::new(path).sort(true).skip_hidden(args.skip_hidden);
+    /// Restrict the target to `length` bytes starting at `offset`, or everything after
+    /// `offset` if there's no length. The range is clamped to the end of the file.
+    fn with_range(self, offset: u64, length: Option<u64>) -> std::io::Result<Self> {
+        match self {
+            TargetType::MMap(mmap, range) => {
+                let start = range.end.min(range.start.saturating_add(offset as usize));
+                let end = match length {
+                    Some(length) => range.end.min(start.saturating_add(length as usize)),
+                    None => range.end,
+                };
 
-    if args.verbose {
-        if args.skip_hidden {
-            println!("Skipping hidden files.");
-        } else {
-            println!("Not skipping hidden files.");
+                Ok(TargetType::MMap(mmap, start..end))
+            }
+            TargetType::File(mut file) => {
+                file.get_mut().seek(SeekFrom::Start(offset))?;
+                file.set_limit(length.unwrap_or(u64::MAX));
+
+                Ok(TargetType::File(file))
+            }
         }
     }
 
-    if let Some(max_threads) = args.max_threads {
-        ThreadPoolBuilder::new()
-            .num_threads(max_threads)
-            .thread_name(|i| format!("dircs-thread-{i}"))
-            .build_global()?;
+    /// Read the entire target, passing each chunk of bytes to `f`. Memmapped targets are
+    /// passed in one go. Returns the number of bytes read.
+    fn read_chunks(self, mut f: impl FnMut(&[u8])) -> std::io::Result<usize> {
+        match self {
+            TargetType::MMap(mmap, range) => {
+                let bytes = &mmap[range];
+                f(bytes);
 
-        if max_threads == 1 {
-            walker = walker.parallelism(jwalk::Parallelism::Serial);
-        } else {
-            walker = walker.parallelism(jwalk::Parallelism::RayonNewPool(max_threads));
+                Ok(bytes.len())
+            }
+            TargetType::File(mut file) => {
+                const BUFFER_SIZE: usize = 64 * 1024; // 64 KiB buffer size.
+                let mut buffer = [0; BUFFER_SIZE];
+                let mut total_bytes = 0;
+
+                loop {
+                    match file.read(&mut buffer) {
+                        Ok(0) => {
+                            return Ok(total_bytes);
+                        }
+                        Ok(bytes_read) => {
+                            total_bytes += bytes_read;
+                            f(&buffer[..bytes_read]);
+                        }
+                        Err(err) => {
+                            if err.kind() == std::io::ErrorKind::Interrupted {
+                                continue;
+                            } else {
+                                return Err(err);
+                            }
+                        }
+                    }
+                }
+            }
         }
+    }
+}
 
-        if args.verbose {
-            if max_threads == 1 {
-                println!("Using {max_threads} thread for file traversal.");
-            } else {
-                println!("Using {max_threads} threads for file traversal.");
+/// A file's hash, along with the hash of each of its blocks when `--piecewise` is used.
+struct FileHash {
+    path: PathBuf,
+    hash: Vec<u8>,
+    pieces: Vec<Vec<u8>>,
+    bytes_read: usize,
+}
+
+/// Get the hashes to output for a path, labelled with what they're the hash of. Most
+/// formats have a single hash for the whole path, but some have one for each file.
+fn get_path_hashes(
+    args: &Args,
+    path: &Path,
+    cache: Option<&HashCache>,
+) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    #[cfg(feature = "sha2")]
+    if args.format == Format::FsVerity {
+        return Ok(formats::fs_verity::get_hashes(args, path)?
+            .into_iter()
+            .map(|(path, hash)| (path.to_string_lossy().into_owned(), hash))
+            .collect());
+    }
+
+    if let Some(piece_size) = args.piecewise {
+        let (hash, file_hashes) = hash_files(args, path, cache)?;
+        let mut hashes = Vec::new();
+
+        for file_hash in file_hashes {
+            let file_path = file_hash.path.to_string_lossy();
+
+            let end = args.offset + file_hash.bytes_read as u64;
+            for (index, piece) in file_hash.pieces.into_iter().enumerate() {
+                let start = args.offset + index as u64 * piece_size;
+                let last = end.min(start + piece_size) - 1;
+                hashes.push((format!("{file_path} [{start}-{last}]"), piece));
+            }
+
+            if path.is_dir() {
+                hashes.push((file_path.into_owned(), file_hash.hash));
             }
         }
+
+        hashes.push((path.to_string_lossy().into_owned(), hash));
+
+        return Ok(hashes);
     }
 
-    if let Some(depth) = args.depth {
-        walker = walker.max_depth(depth);
+    Ok(vec![(
+        path.to_string_lossy().into_owned(),
+        get_path_hash(args, path, cache)?,
+    )])
+}
 
-        if args.verbose {
-            println!("Setting a max depth of {depth} for file traversal.");
-        }
+fn get_path_hash(args: &Args, path: &Path, cache: Option<&HashCache>) -> anyhow::Result<Vec<u8>> {
+    #[cfg(feature = "git")]
+    if let Some(rev) = &args.git_rev {
+        return git::get_rev_hash(args, path, rev);
+    }
+
+    match args.format {
+        Format::Dircs => {}
+        #[cfg(feature = "sha1")]
+        Format::Git => return formats::git::get_hash(args, path),
+        Format::Nar => return formats::nar::get_hash(args, path),
+        #[cfg(feature = "sha2")]
+        Format::Go => return formats::go::get_hash(args, path),
+        #[cfg(feature = "sha2")]
+        Format::FsVerity => unreachable!("fs-verity digests are computed per file"),
+        Format::Structure => return formats::structure::get_hash(args, path),
     }
 
-    let hasher = DircsHasher::new(args.hash);
+    Ok(hash_files(args, path, cache)?.0)
+}
+
+/// Hash each file in a path with dircs' own format, returning the combined hash along with
+/// the hash of each file when `--piecewise` is used.
+fn hash_files(
+    args: &Args,
+    path: &Path,
+    cache: Option<&HashCache>,
+) -> anyhow::Result<(Vec<u8>, Vec<FileHash>)> {
+    let walker = build_walker(args, path)?;
+
+    let hasher = DircsHasher::new(args.hash_function());
 
     #[cfg(feature = "progress")]
     let progress_bar_state = (args.progress && !args.verbose).then(ProgressBarState::default);
@@ -95,7 +224,8 @@ fn get_path_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
         .par_bridge()
         .filter_map(|(index, entry)| {
             if let Ok(entry) = entry {
-                let Ok(path) = entry.path().canonicalize() else {
+                let entry_path = entry.path();
+                let Ok(path) = entry_path.canonicalize() else {
                     if args.verbose {
                         println!(
                             "{} no longer exists, skipping",
@@ -105,7 +235,9 @@ fn get_path_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
                     return None;
                 };
 
-                if path.is_dir() {
+                // The root directory's metadata is left out, as copies of a tree rarely keep it.
+                let is_dir = path.is_dir();
+                if is_dir && (args.include_metadata.is_empty() || entry.depth == 0) {
                     return None;
                 }
 
@@ -116,15 +248,31 @@ fn get_path_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
                     return None;
                 };
 
-                let target = if args.memmap {
-                    match try_memmap(&file) {
-                        Ok(Some(mmap)) => TargetType::MMap(Cursor::new(mmap)),
-                        _ => TargetType::File(file),
-                    }
+                #[cfg(unix)]
+                let xattr_namespaces = &args.xattr_namespace;
+                #[cfg(not(unix))]
+                let xattr_namespaces = &[];
+
+                let metadata_record = if args.include_metadata.is_empty() {
+                    None
                 } else {
-                    TargetType::File(file)
+                    match metadata::read_record(&file, &args.include_metadata, xattr_namespaces) {
+                        Ok(record) => Some(record),
+                        Err(err) => {
+                            if args.verbose {
+                                println!("Couldn't read metadata for {path:?} because: `{err:?}`. Skipping.");
+                            }
+                            return None;
+                        }
+                    }
                 };
 
+                // Directories have no contents, so only their metadata goes into the hash.
+                if is_dir {
+                    let record = metadata_record?;
+                    return Some((index, metadata::hash_record(hasher.clone(), &[], &record), None));
+                }
+
                 #[cfg(feature = "progress")]
                 if let Some(progress_bar_state) = &progress_bar_state {
                     progress_bar_state.update_length();
@@ -134,7 +282,36 @@ fn get_path_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
                     }
                 }
 
-                let hash_result = hasher.clone().hash_target(target);
+                let mut pieces = Vec::new();
+                let hash_result = match (cache, args.sample) {
+                    (_, Some(blocks)) => hasher.clone().hash_sampled(file, blocks, args.memmap),
+                    (Some(cache), None) => {
+                        cache.hash_file(&hasher, args.hash_function(), file, args.memmap)
+                    }
+                    (None, None) => TargetType::new(file, args.memmap)
+                        .with_range(args.offset, args.length)
+                        .map_err(Into::into)
+                        .and_then(|target| match args.piecewise {
+                            Some(piece_size) => hasher
+                                .clone()
+                                .hash_target_piecewise(target, piece_size)
+                                .map(|(result, file_pieces, bytes_read)| {
+                                    pieces = file_pieces;
+                                    (result, bytes_read)
+                                }),
+                            None => hasher.clone().hash_target(target),
+                        }),
+                };
+                let hash_result = hash_result.map(|(result, bytes_read)| {
+                    let result = match &metadata_record {
+                        Some(metadata_record) => {
+                            metadata::hash_record(hasher.clone(), &result, metadata_record)
+                        }
+                        None => result,
+                    };
+
+                    (result, bytes_read)
+                });
 
                 #[cfg(feature = "progress")]
                 if let Some(progress_bar_state) = &progress_bar_state {
@@ -151,7 +328,14 @@ fn get_path_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
                             let hex = hex::encode(&result);
                             println!("{path:?} -> {hex} ({bytes_read} bytes read)",);
                         }
-                        Some((index, result))
+                        let file_hash = args.piecewise.is_some().then(|| FileHash {
+                            path: entry_path,
+                            hash: result.clone(),
+                            pieces,
+                            bytes_read,
+                        });
+
+                        Some((index, result, file_hash))
                     }
                     Err(err) => {
                         if args.verbose {
@@ -175,6 +359,23 @@ fn get_path_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
         progress_bar_state.finish();
     }
 
+    // Sort by index, so that per-file hashes are in traversal order.
+    file_hash_results.sort_by_key(|(index, ..)| *index);
+    let (file_hash_results, file_hashes): (Vec<_>, Vec<_>) = file_hash_results
+        .into_iter()
+        .map(|(index, result, file_hash)| ((index, result), file_hash))
+        .unzip();
+    let file_hashes = file_hashes.into_iter().flatten().collect();
+
+    Ok((combine_file_hashes(hasher, file_hash_results)?, file_hashes))
+}
+
+/// Combine the hashes of individual files into one final hash. Each hash is paired
+/// with the index of the file in traversal order.
+fn combine_file_hashes(
+    hasher: DircsHasher,
+    mut file_hash_results: Vec<(usize, Vec<u8>)>,
+) -> anyhow::Result<Vec<u8>> {
     if file_hash_results.is_empty() {
         bail!("there were no files to hash");
     } else if file_hash_results.len() == 1 {
@@ -186,6 +387,30 @@ fn get_path_hash(args: &Args, path: &Path) -> anyhow::Result<Vec<u8>> {
     }
 }
 
+/// Convert raw bytes from a manifest or repository into a path.
+#[cfg(any(
+    feature = "git",
+    feature = "md5",
+    all(feature = "sha1", feature = "sha2")
+))]
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> anyhow::Result<PathBuf> {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    Ok(PathBuf::from(OsStr::from_bytes(bytes)))
+}
+
+/// Convert raw bytes from a manifest or repository into a path.
+#[cfg(any(
+    feature = "git",
+    feature = "md5",
+    all(feature = "sha1", feature = "sha2")
+))]
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> anyhow::Result<PathBuf> {
+    Ok(PathBuf::from(std::str::from_utf8(bytes)?))
+}
+
 fn verify_args(args: &Args) -> anyhow::Result<()> {
     if let Some(max_threads) = args.max_threads {
         if max_threads == 0 {
@@ -193,6 +418,59 @@ fn verify_args(args: &Args) -> anyhow::Result<()> {
         }
     }
 
+    // Subcommands have to come first, as anything after a path or option is taken as a path.
+    if args.command.is_none() {
+        let command = Args::command();
+
+        for path in &args.paths {
+            let Some(name) = path.to_str() else {
+                continue;
+            };
+
+            if command.find_subcommand(name).is_some() && !path.exists() {
+                bail!("the `{name}` subcommand must come before any paths or options");
+            }
+        }
+    }
+
+    args.format.check_hash_function(args.hash_function())?;
+
+    #[cfg(feature = "sha2")]
+    if args.go_prefix.is_some() && args.format != Format::Go {
+        bail!("--go-prefix is only supported by the go format");
+    }
+
+    if args.format != Format::Dircs {
+        let dircs_only = [
+            ("--include-metadata", !args.include_metadata.is_empty()),
+            ("--cache", args.cache),
+            ("--sample", args.sample.is_some()),
+            ("--offset", args.offset != 0),
+            ("--length", args.length.is_some()),
+            ("--piecewise", args.piecewise.is_some()),
+            #[cfg(feature = "git")]
+            ("--git-rev", args.git_rev.is_some()),
+        ];
+
+        if let Some((option, _)) = dircs_only.iter().find(|(_, used)| *used) {
+            bail!("{option} is only supported by the dircs format");
+        }
+    }
+
+    #[cfg(unix)]
+    if !args.xattr_namespace.is_empty()
+        && !args
+            .include_metadata
+            .contains(&metadata::MetadataField::Xattr)
+    {
+        bail!("--xattr-namespace requires `--include-metadata xattr`");
+    }
+
+    #[cfg(not(unix))]
+    if args.cache {
+        bail!("--cache is only supported on Unix");
+    }
+
     Ok(())
 }
 
@@ -203,13 +481,75 @@ fn main() -> anyhow::Result<()> {
     verify_args(&args)?;
     args.sort_args();
 
-    for path in &args.paths {
-        match get_path_hash(&args, path) {
-            Ok(hash) => {
-                let hex = hex::encode(hash);
-                let path = path.to_string_lossy();
+    if let Some(max_threads) = args.max_threads {
+        ThreadPoolBuilder::new()
+            .num_threads(max_threads)
+            .thread_name(|i| format!("dircs-thread-{i}"))
+            .build_global()?;
+    }
+
+    if let Some(command) = &args.command {
+        return match command {
+            Command::Check { kind, paths, root } => {
+                check::run(&args, *kind, paths, root.as_deref())
+            }
+            Command::Manifest { path, output } => manifest::write(&args, path, output.as_deref()),
+            Command::Status {
+                manifest,
+                path,
+                confirm,
+            } => manifest::status(&args, manifest, path, *confirm),
+            Command::Dupes {
+                paths,
+                json,
+                link,
+                dry_run,
+            } => dupes::dupes(&args, paths, *json, *link, *dry_run),
+            Command::Bao { command } => match command {
+                BaoCommand::Encode { paths } => bao::encode(&args, paths),
+                BaoCommand::Verify {
+                    file,
+                    root_hash,
+                    outboard,
+                    offset,
+                    length,
+                } => bao::verify(
+                    &args,
+                    file,
+                    root_hash,
+                    outboard.as_deref(),
+                    *offset,
+                    *length,
+                ),
+            },
+            Command::Chunks { paths, avg_size } => chunks::chunks(&args, paths, *avg_size),
+            Command::Cmp {
+                a,
+                b,
+                block_size,
+                first,
+            } => cmp::cmp(a, b, *block_size, *first),
+            #[cfg(unix)]
+            Command::Tag { paths } => tag::tag(&args, paths),
+            #[cfg(unix)]
+            Command::Scrub { paths } => tag::scrub(&args, paths),
+        };
+    }
 
-                println!("{path} -> {hex}");
+    let cache = args.cache.then(HashCache::load).transpose()?;
+
+    for path in &args.paths {
+        match get_path_hashes(&args, path, cache.as_ref()) {
+            Ok(hashes) => {
+                for (path, hash) in hashes {
+                    let hash = args.format.encode(args.hash_function(), &hash);
+
+                    if args.sample.is_some() {
+                        println!("{path} -> {hash} (sampled)");
+                    } else {
+                        println!("{path} -> {hash}");
+                    }
+                }
             }
             Err(err) => {
                 let path = path.to_string_lossy();
@@ -228,5 +568,9 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
+    if let Some(cache) = cache {
+        cache.save()?;
+    }
+
     Ok(())
 }
@@ -0,0 +1,308 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, Metadata},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use clap::ValueEnum;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    TargetType,
+    args::Args,
+    hashers::{DircsHasher, HashFunction},
+    walk::build_walker,
+};
+
+const HEADER: &str = "# dircs manifest v1";
+
+/// A file's entry in a manifest.
+struct Entry {
+    hash: Vec<u8>,
+    size: u64,
+    mtime_ns: i128,
+    inode: u64,
+}
+
+impl Entry {
+    /// Whether the file's metadata still matches this entry.
+    fn matches(&self, metadata: &Metadata) -> bool {
+        self.size == metadata.len()
+            && self.mtime_ns == mtime_ns(metadata)
+            && self.inode == inode(metadata)
+    }
+}
+
+/// Write a manifest of every file in a path, with each file's hash and the metadata
+/// used by `status` to quickly find changed files.
+///
+/// The manifest is a header line followed by `<hash> <size> <mtime_ns> <inode> <path>`
+/// lines, where paths are relative to the path the manifest was made from.
+pub(crate) fn write(args: &Args, path: &Path, output: Option<&Path>) -> anyhow::Result<()> {
+    let hash_function = args.hash_function();
+
+    let entries = list_files(args, path)?
+        .into_par_iter()
+        .map(|(name, file_path)| {
+            let file = File::open(&file_path)
+                .with_context(|| format!("could not open {}", file_path.to_string_lossy()))?;
+            let metadata = file.metadata()?;
+            let (hash, bytes_read) =
+                DircsHasher::new(hash_function).hash_target(TargetType::new(file, args.memmap))?;
+
+            if args.verbose {
+                let hex = hex::encode(&hash);
+                println!("{file_path:?} -> {hex} ({bytes_read} bytes read)");
+            }
+
+            let entry = Entry {
+                hash,
+                size: metadata.len(),
+                mtime_ns: mtime_ns(&metadata),
+                inode: inode(&metadata),
+            };
+
+            Ok((name, entry))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(match output {
+        Some(output) => Box::new(
+            File::create(output)
+                .with_context(|| format!("could not create {}", output.to_string_lossy()))?,
+        ),
+        None => Box::new(std::io::stdout().lock()),
+    });
+
+    writeln!(writer, "{HEADER} {}", algorithm_name(hash_function))?;
+
+    for (name, entry) in entries {
+        write!(
+            writer,
+            "{} {} {} {} ",
+            hex::encode(&entry.hash),
+            entry.size,
+            entry.mtime_ns,
+            entry.inode
+        )?;
+        writer.write_all(&name)?;
+        writeln!(writer)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Compare a path against a manifest using only file metadata, listing files that have
+/// probably changed. If `confirm` is set, files whose metadata changed are hashed to
+/// check whether their contents really did. Returns an error if anything changed.
+pub(crate) fn status(
+    args: &Args,
+    manifest_path: &Path,
+    path: &Path,
+    confirm: bool,
+) -> anyhow::Result<()> {
+    let (hash_function, mut manifest) = read(manifest_path)?;
+
+    let mut candidates = Vec::new();
+    let mut added = Vec::new();
+
+    for (name, file_path) in list_files(args, path)? {
+        match manifest.remove(&name) {
+            Some(entry) => {
+                let unchanged =
+                    fs::metadata(&file_path).is_ok_and(|metadata| entry.matches(&metadata));
+
+                if !unchanged {
+                    candidates.push((name, file_path, entry));
+                }
+            }
+            None => added.push(name),
+        }
+    }
+
+    // Anything left in the manifest wasn't found.
+    let deleted = manifest.into_keys().collect::<Vec<_>>();
+
+    let modified = if confirm {
+        let mut modified = candidates
+            .into_par_iter()
+            .filter_map(|(name, file_path, entry)| {
+                let changed = File::open(&file_path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| {
+                        DircsHasher::new(hash_function)
+                            .hash_target(TargetType::new(file, args.memmap))
+                    })
+                    .is_ok_and(|(hash, _)| hash != entry.hash);
+
+                if args.verbose && !changed {
+                    println!(
+                        "{} has different metadata, but the same contents.",
+                        file_path.to_string_lossy()
+                    );
+                }
+
+                // Files that can't be read are reported as modified.
+                changed.then_some(name)
+            })
+            .collect::<Vec<_>>();
+
+        modified.sort();
+        modified
+    } else {
+        candidates.into_iter().map(|(name, _, _)| name).collect()
+    };
+
+    for (label, names) in [
+        ("modified", &modified),
+        ("deleted", &deleted),
+        ("new", &added),
+    ] {
+        for name in names {
+            println!("{label}: {}", String::from_utf8_lossy(name));
+        }
+    }
+
+    if modified.is_empty() && deleted.is_empty() && added.is_empty() {
+        println!("No changes.");
+        return Ok(());
+    }
+
+    println!(
+        "{} modified, {} deleted, {} new.",
+        modified.len(),
+        deleted.len(),
+        added.len()
+    );
+
+    bail!("found changes");
+}
+
+/// Read a manifest, returning the hash function it uses and its entries by name.
+fn read(path: &Path) -> anyhow::Result<(HashFunction, BTreeMap<Vec<u8>, Entry>)> {
+    let contents =
+        fs::read(path).with_context(|| format!("could not read {}", path.to_string_lossy()))?;
+    let mut lines = contents.split(|&byte| byte == b'\n');
+
+    let header = lines.next().unwrap_or_default();
+    let Some(algorithm) = header
+        .strip_prefix(HEADER.as_bytes())
+        .and_then(|algorithm| algorithm.strip_prefix(b" "))
+    else {
+        bail!("{} is not a dircs manifest", path.to_string_lossy());
+    };
+    let algorithm = String::from_utf8_lossy(algorithm);
+    let hash_function = HashFunction::from_str(&algorithm, false)
+        .map_err(|_| anyhow::anyhow!("unknown hash function `{algorithm}`"))?;
+
+    let mut entries = BTreeMap::new();
+
+    for (line_number, line) in lines.enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+
+        let invalid = || {
+            format!(
+                "invalid entry on line {} of {}",
+                line_number + 2,
+                path.to_string_lossy()
+            )
+        };
+
+        let mut fields = line.splitn(5, |&byte| byte == b' ');
+        let mut next = || -> anyhow::Result<&str> {
+            Ok(std::str::from_utf8(fields.next().with_context(invalid)?)?)
+        };
+
+        let entry = Entry {
+            hash: hex::decode(next()?).with_context(invalid)?,
+            size: next()?.parse().with_context(invalid)?,
+            mtime_ns: next()?.parse().with_context(invalid)?,
+            inode: next()?.parse().with_context(invalid)?,
+        };
+        let name = fields.next().with_context(invalid)?;
+
+        entries.insert(name.to_vec(), entry);
+    }
+
+    Ok((hash_function, entries))
+}
+
+/// List the files in a path in traversal order, along with their names relative to
+/// the path. Names always use `/` as a separator.
+fn list_files(args: &Args, path: &Path) -> anyhow::Result<Vec<(Vec<u8>, PathBuf)>> {
+    let mut files = Vec::new();
+
+    for entry in build_walker(args, path)? {
+        let entry = entry?;
+        let entry_path = entry.path();
+
+        // Symlinks are followed, just like when hashing.
+        if entry_path.is_dir() {
+            continue;
+        }
+
+        let relative_path = if entry.depth == 0 {
+            Path::new(&entry.file_name)
+        } else {
+            entry_path.strip_prefix(path)?
+        };
+
+        let mut name = Vec::new();
+        for component in relative_path {
+            if !name.is_empty() {
+                name.push(b'/');
+            }
+
+            name.extend_from_slice(component.as_encoded_bytes());
+        }
+
+        if name.contains(&b'\n') {
+            bail!("file names with newlines are not supported: {entry_path:?}");
+        }
+
+        files.push((name, entry_path));
+    }
+
+    Ok(files)
+}
+
+fn algorithm_name(hash_function: HashFunction) -> String {
+    hash_function
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn mtime_ns(metadata: &Metadata) -> i128 {
+    use std::os::unix::fs::MetadataExt;
+
+    i128::from(metadata.mtime()) * 1_000_000_000 + i128::from(metadata.mtime_nsec())
+}
+
+#[cfg(not(unix))]
+fn mtime_ns(metadata: &Metadata) -> i128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|mtime| mtime.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |mtime| mtime.as_nanos() as i128)
+}
+
+#[cfg(unix)]
+fn inode(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &Metadata) -> u64 {
+    0
+}
@@ -0,0 +1,191 @@
+use std::fs::{File, Metadata};
+
+use clap::ValueEnum;
+
+use crate::hashers::DircsHasher;
+
+/// File metadata that can be included in the hash alongside a file's contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub(crate) enum MetadataField {
+    /// Permission bits, including the setuid, setgid, and sticky bits.
+    #[cfg(unix)]
+    Mode,
+    /// The owner's user ID.
+    #[cfg(unix)]
+    Uid,
+    /// The owner's group ID.
+    #[cfg(unix)]
+    Gid,
+    /// The modification time, to the nanosecond.
+    Mtime,
+    /// Extended attributes, optionally limited to some namespaces with `--xattr-namespace`.
+    #[cfg(unix)]
+    Xattr,
+    /// POSIX ACLs, both the access ACL and a directory's default ACL.
+    #[cfg(unix)]
+    Acl,
+    /// File capabilities, as stored in `security.capability`.
+    #[cfg(unix)]
+    Capability,
+    /// SELinux labels, as stored in `security.selinux`.
+    #[cfg(unix)]
+    Selinux,
+}
+
+impl MetadataField {
+    /// Write the field's `<field>=<value>` lines into a file's record.
+    fn write(
+        self,
+        record: &mut Vec<u8>,
+        file: &File,
+        metadata: &Metadata,
+        xattr_namespaces: &[String],
+    ) -> anyhow::Result<()> {
+        #[cfg(unix)]
+        use std::os::unix::fs::MetadataExt;
+
+        let value = match self {
+            #[cfg(unix)]
+            MetadataField::Mode => format!("{:o}", metadata.mode() & 0o7777),
+            #[cfg(unix)]
+            MetadataField::Uid => metadata.uid().to_string(),
+            #[cfg(unix)]
+            MetadataField::Gid => metadata.gid().to_string(),
+            #[cfg(unix)]
+            MetadataField::Mtime => format!("{}.{:09}", metadata.mtime(), metadata.mtime_nsec()),
+            #[cfg(not(unix))]
+            MetadataField::Mtime => {
+                let mtime = metadata.modified()?.duration_since(std::time::UNIX_EPOCH)?;
+                format!("{}.{:09}", mtime.as_secs(), mtime.subsec_nanos())
+            }
+            #[cfg(unix)]
+            MetadataField::Xattr => {
+                use std::os::unix::ffi::OsStrExt;
+
+                let mut names = xattrs::list(file)?
+                    .into_iter()
+                    .filter(|name| {
+                        xattr_namespaces.is_empty()
+                            || xattr_namespaces.iter().any(|namespace| {
+                                let name = name.as_bytes();
+                                name.starts_with(namespace.as_bytes())
+                                    && name.get(namespace.len()) == Some(&b'.')
+                            })
+                    })
+                    .collect::<Vec<_>>();
+                names.sort();
+
+                for name in names {
+                    let value = xattrs::get(file, &name)?;
+
+                    record.extend_from_slice(b"xattr:");
+                    record.extend_from_slice(name.as_bytes());
+                    record.push(b'=');
+                    record.extend_from_slice(xattrs::encode(value.as_deref()).as_bytes());
+                    record.push(b'\n');
+                }
+
+                return Ok(());
+            }
+            #[cfg(unix)]
+            MetadataField::Acl => {
+                for (kind, name) in [
+                    ("access", "system.posix_acl_access"),
+                    ("default", "system.posix_acl_default"),
+                ] {
+                    let value = xattrs::get(file, name)?;
+                    let line = format!("acl:{kind}={}\n", xattrs::encode(value.as_deref()));
+                    record.extend_from_slice(line.as_bytes());
+                }
+
+                return Ok(());
+            }
+            #[cfg(unix)]
+            MetadataField::Capability => {
+                xattrs::encode(xattrs::get(file, "security.capability")?.as_deref())
+            }
+            #[cfg(unix)]
+            MetadataField::Selinux => {
+                xattrs::encode(xattrs::get(file, "security.selinux")?.as_deref())
+            }
+        };
+
+        #[cfg(not(unix))]
+        let _ = (file, xattr_namespaces);
+
+        record.extend_from_slice(format!("{}={value}\n", self.name()).as_bytes());
+
+        Ok(())
+    }
+
+    fn name(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Read the selected metadata fields of a file into the metadata part of its record.
+/// `fields` should be sorted and deduplicated, so that the order they were specified
+/// in doesn't matter.
+pub(crate) fn read_record(
+    file: &File,
+    fields: &[MetadataField],
+    xattr_namespaces: &[String],
+) -> anyhow::Result<Vec<u8>> {
+    let metadata = file.metadata()?;
+    let mut record = Vec::new();
+
+    for field in fields {
+        field.write(&mut record, file, &metadata, xattr_namespaces)?;
+    }
+
+    Ok(record)
+}
+
+/// Get the hash of a file's record, which is its content hash followed by its metadata.
+pub(crate) fn hash_record(
+    mut hasher: DircsHasher,
+    content_hash: &[u8],
+    metadata_record: &[u8],
+) -> Vec<u8> {
+    hasher.update(content_hash);
+    hasher.update(metadata_record);
+    hasher.finalize()
+}
+
+/// Helpers for reading extended attributes, treating filesystems that don't support
+/// them as having none.
+#[cfg(unix)]
+mod xattrs {
+    use std::{ffi::OsString, fs::File, io::ErrorKind};
+
+    use xattr::FileExt;
+
+    pub(super) fn list(file: &File) -> anyhow::Result<Vec<OsString>> {
+        match file.list_xattr() {
+            Ok(names) => Ok(names.collect()),
+            Err(err) if err.kind() == ErrorKind::Unsupported => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub(super) fn get(
+        file: &File,
+        name: impl AsRef<std::ffi::OsStr>,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        match file.get_xattr(name) {
+            Ok(value) => Ok(value),
+            Err(err) if err.kind() == ErrorKind::Unsupported => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Encode a value so that a missing attribute and an empty one are different.
+    pub(super) fn encode(value: Option<&[u8]>) -> String {
+        match value {
+            Some(value) => format!("0x{}", hex::encode(value)),
+            None => String::new(),
+        }
+    }
+}
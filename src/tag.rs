@@ -0,0 +1,260 @@
+use std::{
+    fs::{File, Metadata},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use clap::ValueEnum;
+use rayon::prelude::{ParallelBridge, ParallelIterator};
+use xattr::FileExt;
+
+use crate::{
+    TargetType,
+    args::Args,
+    hashers::{DircsHasher, HashFunction},
+    walk::build_walker,
+};
+
+const HASH_ATTR: &str = "user.dircs.hash";
+const ALGORITHM_ATTR: &str = "user.dircs.algorithm";
+const SIZE_ATTR: &str = "user.dircs.size";
+const MTIME_ATTR: &str = "user.dircs.mtime";
+
+/// What a file looked like when it was tagged.
+struct Tag {
+    hash: Vec<u8>,
+    hash_function: HashFunction,
+    size: u64,
+    mtime: String,
+}
+
+impl Tag {
+    fn read(file: &File) -> anyhow::Result<Option<Self>> {
+        let get = |name| -> anyhow::Result<Option<String>> {
+            match file.get_xattr(name)? {
+                Some(value) => Ok(Some(String::from_utf8(value)?)),
+                None => Ok(None),
+            }
+        };
+
+        let Some(hash) = get(HASH_ATTR)? else {
+            return Ok(None);
+        };
+
+        let algorithm = get(ALGORITHM_ATTR)?.context("missing algorithm")?;
+        let size = get(SIZE_ATTR)?.context("missing size")?;
+        let mtime = get(MTIME_ATTR)?.context("missing mtime")?;
+
+        Ok(Some(Self {
+            hash: hex::decode(hash)?,
+            hash_function: HashFunction::from_str(&algorithm, false)
+                .map_err(|_| anyhow::anyhow!("unknown algorithm `{algorithm}`"))?,
+            size: size.parse()?,
+            mtime,
+        }))
+    }
+
+    fn write(&self, file: &File) -> anyhow::Result<()> {
+        let algorithm = self
+            .hash_function
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+
+        file.set_xattr(HASH_ATTR, hex::encode(&self.hash).as_bytes())?;
+        file.set_xattr(ALGORITHM_ATTR, algorithm.as_bytes())?;
+        file.set_xattr(SIZE_ATTR, self.size.to_string().as_bytes())?;
+        file.set_xattr(MTIME_ATTR, self.mtime.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Whether the file still looks the same as when it was tagged, going by its
+    /// size and modification time.
+    fn matches(&self, metadata: &Metadata) -> bool {
+        self.size == metadata.len() && self.mtime == mtime(metadata)
+    }
+}
+
+/// What happened to a file while scrubbing.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScrubStatus {
+    Ok,
+    Corrupted,
+    /// The file was modified since it was tagged, so it can't be checked.
+    Changed,
+    Untagged,
+    Failed,
+}
+
+/// Store the hash of every file in the given paths in its extended attributes, along
+/// with the file's size and modification time.
+pub(crate) fn tag(args: &Args, paths: &[PathBuf]) -> anyhow::Result<()> {
+    let hash_function = args.hash_function();
+    let mut results = Vec::new();
+
+    for path in paths {
+        results.extend(map_files(args, path, |file_path| {
+            let result = tag_file(args, file_path, hash_function);
+
+            match &result {
+                Ok(()) => {
+                    if args.verbose {
+                        println!("Tagged {}.", file_path.to_string_lossy());
+                    }
+                }
+                Err(err) => println!("{}: {err}", file_path.to_string_lossy()),
+            }
+
+            result.is_ok()
+        })?);
+    }
+
+    let failed = results.iter().filter(|&&tagged| !tagged).count();
+    println!("Tagged {} files, {failed} failed.", results.len() - failed);
+
+    if failed > 0 {
+        bail!("some files could not be tagged");
+    }
+
+    Ok(())
+}
+
+fn tag_file(args: &Args, path: &Path, hash_function: HashFunction) -> anyhow::Result<()> {
+    let (file, metadata) = open(path)?;
+    let hash = hash_file(args, &file, hash_function)?;
+
+    // Don't tag a file that was modified while we were hashing it.
+    if !unchanged(&file, &metadata)? {
+        bail!("modified while hashing");
+    }
+
+    Tag {
+        hash,
+        hash_function,
+        size: metadata.len(),
+        mtime: mtime(&metadata),
+    }
+    .write(&file)
+}
+
+/// Re-hash every tagged file in the given paths that hasn't been modified since it was
+/// tagged, reporting any whose contents no longer match.
+pub(crate) fn scrub(args: &Args, paths: &[PathBuf]) -> anyhow::Result<()> {
+    let mut statuses = Vec::new();
+
+    for path in paths {
+        statuses.extend(map_files(args, path, |file_path| {
+            let status = scrub_file(args, file_path).unwrap_or_else(|err| {
+                println!("{}: {err}", file_path.to_string_lossy());
+                ScrubStatus::Failed
+            });
+
+            if status == ScrubStatus::Corrupted {
+                println!("CORRUPTED: {}", file_path.to_string_lossy());
+            } else if args.verbose {
+                let status = match status {
+                    ScrubStatus::Ok => "OK",
+                    ScrubStatus::Changed => "changed since tagged, skipping",
+                    ScrubStatus::Untagged => "untagged, skipping",
+                    ScrubStatus::Corrupted | ScrubStatus::Failed => "failed",
+                };
+
+                println!("{}: {status}", file_path.to_string_lossy());
+            }
+
+            status
+        })?);
+    }
+
+    let count = |status| statuses.iter().filter(|&&other| other == status).count();
+    let corrupted = count(ScrubStatus::Corrupted);
+    let failed = count(ScrubStatus::Failed);
+
+    println!(
+        "Scrubbed {} files: {corrupted} corrupted, {} changed since tagged, {} untagged, {failed} failed.",
+        count(ScrubStatus::Ok) + corrupted,
+        count(ScrubStatus::Changed),
+        count(ScrubStatus::Untagged),
+    );
+
+    if corrupted > 0 {
+        bail!("found corrupted files");
+    } else if failed > 0 {
+        bail!("some files could not be scrubbed");
+    }
+
+    Ok(())
+}
+
+fn scrub_file(args: &Args, path: &Path) -> anyhow::Result<ScrubStatus> {
+    let (file, metadata) = open(path)?;
+
+    let Some(tag) = Tag::read(&file)? else {
+        return Ok(ScrubStatus::Untagged);
+    };
+
+    if !tag.matches(&metadata) {
+        return Ok(ScrubStatus::Changed);
+    }
+
+    let hash = hash_file(args, &file, tag.hash_function)?;
+
+    // A file that was modified while we were hashing it isn't corrupted.
+    if !unchanged(&file, &metadata)? {
+        Ok(ScrubStatus::Changed)
+    } else if hash == tag.hash {
+        Ok(ScrubStatus::Ok)
+    } else {
+        Ok(ScrubStatus::Corrupted)
+    }
+}
+
+/// Call `f` in parallel for every regular file in a path, collecting the results.
+fn map_files<T: Send>(
+    args: &Args,
+    path: &Path,
+    f: impl Fn(&Path) -> T + Sync + Send,
+) -> anyhow::Result<Vec<T>> {
+    Ok(build_walker(args, path)?
+        .into_iter()
+        .par_bridge()
+        .filter_map(|entry| match entry {
+            Ok(entry) if entry.file_type().is_file() => Some(f(&entry.path())),
+            Ok(_) => None,
+            Err(err) => {
+                if args.verbose {
+                    println!("Found an issue with entry {err:?}, skipping.");
+                }
+
+                None
+            }
+        })
+        .collect())
+}
+
+fn open(path: &Path) -> anyhow::Result<(File, Metadata)> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+
+    Ok((file, metadata))
+}
+
+fn hash_file(args: &Args, file: &File, hash_function: HashFunction) -> anyhow::Result<Vec<u8>> {
+    let (hash, _) = DircsHasher::new(hash_function)
+        .hash_target(TargetType::new(file.try_clone()?, args.memmap))?;
+
+    Ok(hash)
+}
+
+/// Whether a file's size and modification time are the same as they were before.
+fn unchanged(file: &File, before: &Metadata) -> anyhow::Result<bool> {
+    let after = file.metadata()?;
+
+    Ok(after.len() == before.len() && mtime(&after) == mtime(before))
+}
+
+fn mtime(metadata: &Metadata) -> String {
+    format!("{}.{:09}", metadata.mtime(), metadata.mtime_nsec())
+}
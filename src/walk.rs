@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use jwalk::WalkDirGeneric;
+
+#[cfg(feature = "sha1")]
+use crate::formats::Format;
+use crate::{
+    args::Args,
+    filter::PathFilter,
+    ignore_files::{IgnoreRules, is_cache_dir},
+};
+
+/// The walker used for file traversal. Each directory carries the ignore rules
+/// that apply to its children.
+pub(crate) type Walker = WalkDirGeneric<(IgnoreRules, ())>;
+
+/// Build a walker for a path, based on the passed-in arguments.
+pub(crate) fn build_walker(args: &Args, path: &Path) -> anyhow::Result<Walker> {
+    let mut walker = Walker::new(path).sort(true).skip_hidden(args.skip_hidden);
+
+    if args.verbose {
+        if args.skip_hidden {
+            println!("Skipping hidden files.");
+        } else {
+            println!("Not skipping hidden files.");
+        }
+    }
+
+    if let Some(max_threads) = args.max_threads {
+        if max_threads == 1 {
+            walker = walker.parallelism(jwalk::Parallelism::Serial);
+        } else {
+            walker = walker.parallelism(jwalk::Parallelism::RayonNewPool(max_threads));
+        }
+
+        if args.verbose {
+            if max_threads == 1 {
+                println!("Using {max_threads} thread for file traversal.");
+            } else {
+                println!("Using {max_threads} threads for file traversal.");
+            }
+        }
+    }
+
+    if let Some(depth) = args.depth {
+        walker = walker.max_depth(depth);
+
+        if args.verbose {
+            println!("Setting a max depth of {depth} for file traversal.");
+        }
+    }
+
+    let filter = PathFilter::new(&args.include, &args.exclude)?;
+
+    if args.verbose {
+        for glob in &args.include {
+            println!("Only including files matching `{glob}`.");
+        }
+
+        for glob in &args.exclude {
+            println!("Excluding entries matching `{glob}`.");
+        }
+    }
+
+    #[cfg(feature = "git")]
+    let git_tracked = args.git_tracked;
+    #[cfg(not(feature = "git"))]
+    let git_tracked = false;
+
+    // Ignore rules and tracked files want canonical paths, so we need to keep track of
+    // both what the walker sees and the canonical version to translate between the two.
+    let canonical_root = (args.ignore_files || git_tracked)
+        .then(|| path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+
+    if let Some(canonical_root) = &canonical_root {
+        if args.ignore_files {
+            if args.verbose {
+                println!("Respecting ignore files.");
+            }
+
+            walker = walker.root_read_dir_state(IgnoreRules::for_root(canonical_root));
+        }
+    }
+
+    #[cfg(feature = "git")]
+    let tracked_files = match &canonical_root {
+        Some(canonical_root) if git_tracked => {
+            if args.verbose {
+                println!("Only including files tracked by git.");
+            }
+
+            Some(crate::git::TrackedFiles::load(canonical_root)?)
+        }
+        _ => None,
+    };
+
+    // Git never includes its own directory in a tree.
+    #[cfg(feature = "sha1")]
+    let skip_git_dirs = args.format == Format::Git;
+    #[cfg(not(feature = "sha1"))]
+    let skip_git_dirs = false;
+
+    if filter.is_none() && canonical_root.is_none() && !skip_git_dirs {
+        return Ok(walker);
+    }
+
+    let ignore_files = args.ignore_files;
+
+    let root = path.to_path_buf();
+    let walker = walker.process_read_dir(move |depth, dir_path, ignore_rules, children| {
+        // A depth of `None` means we're looking at the root itself, which we never filter.
+        if depth.is_none() {
+            return;
+        }
+
+        let relative_path =
+            |path: &Path| -> PathBuf { path.strip_prefix(&root).unwrap_or(path).to_path_buf() };
+
+        if let (Some(canonical_root), true) = (&canonical_root, ignore_files) {
+            // Collecting the components drops the trailing separator `join` adds for the root.
+            let canonical_dir: PathBuf = canonical_root
+                .join(relative_path(dir_path))
+                .components()
+                .collect();
+
+            if is_cache_dir(&canonical_dir) {
+                children.clear();
+                return;
+            }
+
+            *ignore_rules = ignore_rules.with_dir(&canonical_dir);
+        }
+
+        children.retain(|child| match child {
+            Ok(entry) => {
+                let is_dir = entry.file_type.is_dir();
+
+                if skip_git_dirs && is_dir && entry.file_name == ".git" {
+                    return false;
+                }
+
+                let relative_path = relative_path(&entry.parent_path.join(&entry.file_name));
+
+                if let Some(filter) = &filter {
+                    if !filter.is_allowed(&relative_path, is_dir) {
+                        return false;
+                    }
+                }
+
+                if let Some(canonical_root) = &canonical_root {
+                    let canonical_path = canonical_root.join(&relative_path);
+
+                    if ignore_files && ignore_rules.is_ignored(&canonical_path, is_dir) {
+                        return false;
+                    }
+
+                    #[cfg(feature = "git")]
+                    if let Some(tracked_files) = &tracked_files {
+                        if !tracked_files.contains(&canonical_path, is_dir) {
+                            return false;
+                        }
+                    }
+                }
+
+                true
+            }
+            Err(_) => true,
+        });
+    });
+
+    Ok(walker)
+}
@@ -0,0 +1,55 @@
+#![allow(missing_docs)]
+
+use std::process::Command;
+
+fn dircs() -> Command {
+    let exe = env!("CARGO_BIN_EXE_dircs");
+    Command::new(exe)
+}
+
+#[test]
+fn bao_encode_and_verify() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("media.bin");
+    let mut contents = (0..300_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(&file, &contents).unwrap();
+
+    let out = dircs().args(["bao", "encode"]).arg(&file).output().unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let hash = stdout.rsplit_once(' ').unwrap().1.trim_end().to_string();
+
+    // The hash is the file's plain BLAKE3 hash.
+    let plain = dircs().arg(&file).output().unwrap();
+    assert!(
+        String::from_utf8(plain.stdout)
+            .unwrap()
+            .ends_with(&format!("{hash}\n"))
+    );
+
+    // Header plus one parent node for each chunk after the first.
+    let outboard = dir.path().join("media.bin.obao");
+    assert_eq!(std::fs::metadata(&outboard).unwrap().len(), 8 + 292 * 64);
+
+    let verify = |args: &[&str]| {
+        dircs()
+            .args(["bao", "verify"])
+            .arg(&file)
+            .arg(&hash)
+            .args(args)
+            .output()
+            .unwrap()
+            .status
+            .success()
+    };
+
+    assert!(verify(&[]));
+    assert!(verify(&["--offset", "100000", "--length", "5000"]));
+    assert!(!verify(&["--offset", "299999", "--length", "2"]));
+
+    // Only slices that overlap the corrupted chunk fail.
+    contents[200_000] ^= 1;
+    std::fs::write(&file, &contents).unwrap();
+    assert!(verify(&["--offset", "100000", "--length", "5000"]));
+    assert!(!verify(&["--offset", "199000", "--length", "5000"]));
+    assert!(!verify(&[]));
+}
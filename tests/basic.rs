@@ -128,3 +128,337 @@ fn multiple_inputs() {
 
     assert_eq!(String::from_utf8(out.stdout).unwrap(), correct);
 }
+
+#[test]
+fn subcommand_after_args() {
+    let succeeds = |args: &[&str]| dircs().args(args).output().unwrap().status.success();
+
+    assert!(!succeeds(&[
+        "./tests/test_dir",
+        "dupes",
+        "./tests/test_dir"
+    ]));
+    assert!(!succeeds(&["--skip-hidden", "dupes", "./tests/test_dir"]));
+    assert!(succeeds(&["dupes", "--skip-hidden", "./tests/test_dir"]));
+    assert!(succeeds(&["dupes", "./tests/test_dir", "--skip-hidden"]));
+}
+
+#[test]
+fn exclude_glob() {
+    let out = dircs()
+        .args(["./tests/test_dir", "--exclude", "sub_dir"])
+        .output()
+        .unwrap();
+    let excluded = String::from_utf8(out.stdout).unwrap();
+
+    let out = dircs()
+        .args(["./tests/test_dir", "--include", "*.txt"])
+        .output()
+        .unwrap();
+    let top_level_only = String::from_utf8(out.stdout).unwrap();
+
+    assert_eq!(excluded, top_level_only);
+    assert_ne!(
+        excluded.rsplit_once(' ').unwrap().1.trim_end(),
+        get_hash("./tests/test_dir")
+    );
+}
+
+#[test]
+fn include_glob() {
+    let out = dircs()
+        .args(["./tests/test_dir", "--include", "**/c.txt"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+
+    assert_eq!(
+        stdout.rsplit_once(' ').unwrap().1.trim_end(),
+        get_hash("./tests/test_dir/sub_dir/c.txt")
+    );
+}
+
+#[test]
+fn ignore_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    std::fs::create_dir_all(root.join("sub/cache")).unwrap();
+    std::fs::create_dir_all(root.join("target")).unwrap();
+    std::fs::write(root.join("a.txt"), "a\n").unwrap();
+    std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+    std::fs::write(root.join(".dircsignore"), ".*ignore\n").unwrap();
+    std::fs::write(root.join("sub/.gitignore"), "!keep.log\n").unwrap();
+    std::fs::write(root.join("sub/keep.log"), "b\n").unwrap();
+    std::fs::write(root.join("sub/skip.log"), "skip").unwrap();
+    std::fs::write(root.join("target/out"), "skip").unwrap();
+    std::fs::write(
+        root.join("sub/cache/CACHEDIR.TAG"),
+        "Signature: 8a477f597d28d172789f06886806bc55\n",
+    )
+    .unwrap();
+
+    let out = dircs().arg(root).arg("-i").output().unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let hash = stdout.rsplit_once(' ').unwrap().1.trim_end();
+
+    // Only `a.txt` and `sub/keep.log` should remain, which have the same contents as
+    // `a.txt` and `b.txt` in the test directory.
+    let out = dircs()
+        .args(["./tests/test_dir", "--exclude", "sub_dir"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+
+    assert_eq!(hash, stdout.rsplit_once(' ').unwrap().1.trim_end());
+}
+
+#[cfg(unix)]
+#[test]
+fn include_metadata() {
+    use std::{fs, os::unix::fs::PermissionsExt, time::SystemTime};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    fs::write(root.join("a.txt"), "a\n").unwrap();
+    fs::write(root.join("run.sh"), "#!/bin/sh\n").unwrap();
+    fs::set_permissions(root.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    let hash = |args: &[&str]| {
+        let out = dircs().arg(root).args(args).output().unwrap();
+        let stdout = String::from_utf8(out.stdout).unwrap();
+
+        stdout.rsplit_once(' ').unwrap().1.trim_end().to_string()
+    };
+
+    let content_only = hash(&[]);
+    let with_mode = hash(&["--include-metadata", "mode"]);
+    assert_ne!(content_only, with_mode);
+
+    // Field order and duplicates don't matter.
+    assert_eq!(
+        hash(&["--include-metadata", "uid,mode"]),
+        hash(&["--include-metadata", "mode,uid,mode"])
+    );
+
+    fs::set_permissions(root.join("run.sh"), fs::Permissions::from_mode(0o644)).unwrap();
+    assert_eq!(hash(&[]), content_only);
+    assert_ne!(hash(&["--include-metadata", "mode"]), with_mode);
+
+    let with_mtime = hash(&["--include-metadata", "mtime"]);
+    fs::File::options()
+        .write(true)
+        .open(root.join("a.txt"))
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    assert_ne!(hash(&["--include-metadata", "mtime"]), with_mtime);
+    assert_eq!(hash(&[]), content_only);
+
+    // Directories' metadata is included as well.
+    fs::create_dir(root.join("sub")).unwrap();
+    let with_mode = hash(&["--include-metadata", "mode"]);
+    fs::set_permissions(root.join("sub"), fs::Permissions::from_mode(0o700)).unwrap();
+    assert_ne!(hash(&["--include-metadata", "mode"]), with_mode);
+    assert_eq!(hash(&[]), content_only);
+}
+
+#[cfg(unix)]
+#[test]
+fn include_xattrs() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    std::fs::write(root.join("a.txt"), "a\n").unwrap();
+
+    let hash = |args: &[&str]| {
+        let out = dircs()
+            .arg(root)
+            .arg("--include-metadata")
+            .args(args)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(out.stdout).unwrap();
+
+        stdout.rsplit_once(' ').unwrap().1.trim_end().to_string()
+    };
+
+    let before = hash(&["xattr"]);
+    let before_security = hash(&["xattr", "--xattr-namespace", "security"]);
+    let before_capability = hash(&["capability"]);
+
+    // Namespaces only make sense when extended attributes are included.
+    let out = dircs()
+        .arg(root)
+        .args(["--include-metadata", "mode", "--xattr-namespace", "user"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+
+    // Not every filesystem supports user extended attributes.
+    if xattr::set(root.join("a.txt"), "user.dircs.test", b"1").is_err() {
+        return;
+    }
+
+    assert_ne!(hash(&["xattr"]), before);
+    assert_eq!(
+        hash(&["xattr", "--xattr-namespace", "security"]),
+        before_security
+    );
+    assert_ne!(
+        hash(&["xattr", "--xattr-namespace", "user"]),
+        before_security
+    );
+    assert_eq!(hash(&["capability"]), before_capability);
+}
+
+#[cfg(unix)]
+#[test]
+fn cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("files");
+
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("a.txt"), "a\n").unwrap();
+    std::fs::write(root.join("b.txt"), "b\n").unwrap();
+
+    // Recently modified files aren't cached.
+    std::thread::sleep(std::time::Duration::from_millis(2100));
+
+    let hash = |verbose: bool| {
+        let mut command = dircs();
+        command
+            .arg(&root)
+            .arg("--cache")
+            .env("XDG_CACHE_HOME", dir.path().join("cache"));
+
+        if verbose {
+            command.arg("-v");
+        }
+
+        String::from_utf8(command.output().unwrap().stdout).unwrap()
+    };
+
+    let first = hash(false);
+    assert!(dir.path().join("cache/dircs/hashes").is_file());
+    assert!(hash(true).contains("(0 bytes read)"));
+    assert_eq!(hash(false), first);
+
+    // Changed files are hashed again.
+    std::fs::write(root.join("b.txt"), "changed\n").unwrap();
+    let uncached = String::from_utf8(dircs().arg(&root).output().unwrap().stdout).unwrap();
+    assert_ne!(uncached, first);
+    assert_eq!(hash(false), uncached);
+
+    // Entries that haven't been used for a long time are dropped.
+    let cache_path = dir.path().join("cache/dircs/hashes");
+    let stale = "1 2 3 4 5 blake3 00 0\n";
+    let mut cache = std::fs::read_to_string(&cache_path).unwrap();
+    cache.push_str(stale);
+    std::fs::write(&cache_path, cache).unwrap();
+
+    assert_eq!(hash(false), uncached);
+    let cache = std::fs::read_to_string(&cache_path).unwrap();
+    assert!(!cache.contains(stale));
+    assert_eq!(cache.lines().count(), 2);
+}
+
+#[test]
+fn sample() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("large.bin");
+    let mut contents = vec![0u8; 4 * 1024 * 1024];
+    std::fs::write(&file, &contents).unwrap();
+
+    let hash = |args: &[&str]| {
+        let out = dircs().arg(&file).args(args).output().unwrap();
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let sampled = hash(&["--sample"]);
+    assert!(sampled.trim_end().ends_with(" (sampled)"));
+    assert_ne!(sampled, hash(&[]));
+
+    // Changes between the sampled blocks aren't noticed, but changes to the start are.
+    contents[100 * 1024] = 1;
+    std::fs::write(&file, &contents).unwrap();
+    assert_eq!(hash(&["--sample"]), sampled);
+    assert_ne!(hash(&["--sample=64"]), hash(&["--sample=8"]));
+
+    contents[0] = 1;
+    std::fs::write(&file, &contents).unwrap();
+    assert_ne!(hash(&["--sample"]), sampled);
+
+    // Small files are hashed in full.
+    let small = hash_with_fn("./tests/test_dir/a.txt", "blake3");
+    let out = dircs()
+        .args(["./tests/test_dir/a.txt", "--sample"])
+        .output()
+        .unwrap();
+    assert!(String::from_utf8(out.stdout).unwrap().contains(&small));
+}
+
+#[test]
+fn byte_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let contents = (0..100_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(dir.path().join("image.bin"), &contents).unwrap();
+    std::fs::write(dir.path().join("payload.bin"), &contents[1000..51_000]).unwrap();
+    std::fs::write(dir.path().join("tail.bin"), &contents[1000..]).unwrap();
+
+    let image = dir.path().join("image.bin");
+    let image = image.to_str().unwrap();
+    let hash = |args: &[&str]| {
+        let out = dircs().arg(image).args(args).output().unwrap();
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        stdout.rsplit_once(' ').unwrap().1.trim_end().to_string()
+    };
+
+    let payload = get_hash(dir.path().join("payload.bin").to_str().unwrap());
+    assert_eq!(hash(&["--offset", "1000", "--length", "50000"]), payload);
+    assert_eq!(
+        hash(&["--offset", "1000", "--length", "50000", "--memmap"]),
+        payload
+    );
+
+    let tail = get_hash(dir.path().join("tail.bin").to_str().unwrap());
+    assert_eq!(hash(&["--offset", "1000"]), tail);
+    assert_eq!(hash(&["--offset", "1000", "--memmap"]), tail);
+    assert_eq!(hash(&["--offset", "1000", "--length", "1000000"]), tail);
+}
+
+#[test]
+fn piecewise() {
+    let dir = tempfile::tempdir().unwrap();
+    let contents = (0..100_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    let file = dir.path().join("backup.bin");
+    std::fs::write(&file, &contents).unwrap();
+
+    for (index, piece) in contents.chunks(40_000).enumerate() {
+        std::fs::write(dir.path().join(format!("piece{index}")), piece).unwrap();
+    }
+
+    let piece_hash =
+        |index: usize| get_hash(dir.path().join(format!("piece{index}")).to_str().unwrap());
+    let file = file.to_str().unwrap();
+    let expected = format!(
+        "{file} [0-39999] -> {}\n\
+         {file} [40000-79999] -> {}\n\
+         {file} [80000-99999] -> {}\n\
+         {file} -> {}\n",
+        piece_hash(0),
+        piece_hash(1),
+        piece_hash(2),
+        get_hash(file)
+    );
+
+    for args in [&[][..], &["--memmap"]] {
+        let out = dircs()
+            .arg(file)
+            .args(["--piecewise", "40000"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8(out.stdout).unwrap(), expected);
+    }
+}
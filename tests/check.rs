@@ -0,0 +1,278 @@
+#![allow(missing_docs)]
+#![cfg(any(feature = "md5", feature = "sha2"))]
+
+use std::{fs, path::Path, process::Command};
+
+fn dircs() -> Command {
+    let exe = env!("CARGO_BIN_EXE_dircs");
+    Command::new(exe)
+}
+
+fn check(kind: &str, path: &Path) -> (bool, String) {
+    let out = dircs().args(["check", kind]).arg(path).output().unwrap();
+
+    (out.status.success(), String::from_utf8(out.stdout).unwrap())
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn check_cargo() {
+    let dir = tempfile::tempdir().unwrap();
+    let vendor = dir.path();
+
+    for name in ["bar", "foo"] {
+        fs::create_dir_all(vendor.join(name).join("src")).unwrap();
+        fs::write(vendor.join(name).join("src/lib.rs"), "hi\n").unwrap();
+        fs::write(vendor.join(name).join("Cargo.toml"), "[package]\n").unwrap();
+        fs::write(
+            vendor.join(name).join(".cargo-checksum.json"),
+            r#"{"files":{
+                "Cargo.toml":"8a3cd5a81b3f9a621aa493d90c45f42ab571d4e42b8ae5aff351cb0a02d06d82",
+                "src/lib.rs":"98ea6e4f216f2fb4b69fff9b3a44842c38686ca685f3f55dc48c5d3fb1107be4"
+            },"package":"00"}"#,
+        )
+        .unwrap();
+    }
+
+    let (success, stdout) = check("cargo", vendor);
+    assert!(success, "{stdout}");
+    assert!(stdout.ends_with("Checked 2 manifests, 0 failed.\n"));
+
+    let (success, _) = check("cargo", &vendor.join("foo"));
+    assert!(success);
+
+    fs::write(vendor.join("foo/src/lib.rs"), "bye\n").unwrap();
+    fs::remove_file(vendor.join("foo/Cargo.toml")).unwrap();
+    fs::write(vendor.join("foo/src/extra.rs"), "").unwrap();
+
+    let (success, stdout) = check("cargo", vendor);
+    assert!(!success);
+    assert_eq!(
+        stdout,
+        "foo: FAILED\n  modified: src/lib.rs\n  missing: Cargo.toml\n  unlisted: src/extra.rs\nChecked 2 manifests, 1 failed.\n"
+    );
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn check_record() {
+    let dir = tempfile::tempdir().unwrap();
+    let site_packages = dir.path();
+
+    fs::create_dir_all(site_packages.join("pkg")).unwrap();
+    fs::create_dir_all(site_packages.join("pkg-1.0.dist-info")).unwrap();
+    fs::write(site_packages.join("pkg/__init__.py"), "hi\n").unwrap();
+    fs::write(site_packages.join("pkg/a,b.py"), "").unwrap();
+    fs::write(
+        site_packages.join("pkg-1.0.dist-info/RECORD"),
+        "pkg/__init__.py,sha256=mOpuTyFvL7S2n_-bOkSELDhobKaF8_VdxIxdP7EQe-Q,3\n\
+         \"pkg/a,b.py\",sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0\n\
+         pkg/__pycache__/__init__.cpython-313.pyc,,\n\
+         pkg-1.0.dist-info/RECORD,,\n",
+    )
+    .unwrap();
+
+    let (success, stdout) = check("record", site_packages);
+    assert!(success, "{stdout}");
+
+    let (success, _) = check("record", &site_packages.join("pkg-1.0.dist-info"));
+    assert!(success);
+
+    fs::write(site_packages.join("pkg/__init__.py"), "ho\n").unwrap();
+    fs::remove_file(site_packages.join("pkg/a,b.py")).unwrap();
+
+    let (success, stdout) = check("record", site_packages);
+    assert!(!success);
+    assert_eq!(
+        stdout,
+        "pkg-1.0.dist-info: FAILED\n  modified: pkg/__init__.py\n  missing: pkg/a,b.py\nChecked 1 manifest, 1 failed.\n"
+    );
+
+    // Files that can't be read aren't reported as missing or modified.
+    fs::create_dir(site_packages.join("pkg/a,b.py")).unwrap();
+
+    let (success, stdout) = check("record", site_packages);
+    assert!(!success);
+    assert!(stdout.contains("  error: pkg/a,b.py: "), "{stdout}");
+    assert!(!stdout.contains("missing"));
+}
+
+#[cfg(feature = "md5")]
+#[test]
+fn check_md5sums() {
+    let dir = tempfile::tempdir().unwrap();
+    let package = dir.path().join("pkg");
+
+    fs::create_dir_all(package.join("DEBIAN")).unwrap();
+    fs::create_dir_all(package.join("usr/share/doc/pkg")).unwrap();
+    fs::write(package.join("usr/share/doc/pkg/README"), "hi\n").unwrap();
+    fs::write(package.join("usr/share/doc/pkg/with space"), "").unwrap();
+
+    let md5sums = "764efa883dda1e11db47671c4a3bbd9e  usr/share/doc/pkg/README\n\
+                   d41d8cd98f00b204e9800998ecf8427e  usr/share/doc/pkg/with space\n";
+    fs::write(package.join("DEBIAN/md5sums"), md5sums).unwrap();
+
+    let (success, stdout) = check("md5sums", &package);
+    assert!(success, "{stdout}");
+
+    // Installed packages keep their manifests separately from their files.
+    let info = dir.path().join("info");
+    fs::create_dir_all(&info).unwrap();
+    fs::write(info.join("pkg.md5sums"), md5sums).unwrap();
+
+    let out = dircs()
+        .args(["check", "md5sums"])
+        .arg(&info)
+        .arg("--root")
+        .arg(&package)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    fs::write(package.join("usr/share/doc/pkg/README"), "ho\n").unwrap();
+
+    let out = dircs()
+        .args(["check", "md5sums"])
+        .arg(info.join("pkg.md5sums"))
+        .arg("--root")
+        .arg(&package)
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "pkg: FAILED\n  modified: usr/share/doc/pkg/README\nChecked 1 manifest, 1 failed.\n"
+    );
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn check_oci() {
+    const LAYER: &str = "4970405cb2a3a461cc00fd755712beded51919d7e69270d7d10d0dcf5e209714";
+    const CONFIG: &str = "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a";
+    const MANIFEST: &str = "559b2bbe51d2140d08ff840ecb7f2292dd3279b2f7c8540f48091555db12253c";
+
+    let dir = tempfile::tempdir().unwrap();
+    let layout = dir.path().join("image");
+    let blobs = layout.join("blobs/sha256");
+
+    fs::create_dir_all(&blobs).unwrap();
+    fs::write(
+        layout.join("oci-layout"),
+        r#"{"imageLayoutVersion":"1.0.0"}"#,
+    )
+    .unwrap();
+    fs::write(blobs.join(LAYER), "layer\n").unwrap();
+    fs::write(blobs.join(CONFIG), "{}").unwrap();
+    fs::write(
+        blobs.join(MANIFEST),
+        format!(
+            r#"{{"schemaVersion":2,"mediaType":"application/vnd.oci.image.manifest.v1+json","config":{{"mediaType":"application/vnd.oci.image.config.v1+json","digest":"sha256:{CONFIG}","size":2}},"layers":[{{"mediaType":"application/vnd.oci.image.layer.v1.tar","digest":"sha256:{LAYER}","size":6}}]}}"#
+        ),
+    )
+    .unwrap();
+    fs::write(
+        layout.join("index.json"),
+        format!(
+            r#"{{"schemaVersion":2,"manifests":[{{"mediaType":"application/vnd.oci.image.manifest.v1+json","digest":"sha256:{MANIFEST}","size":392}}]}}"#
+        ),
+    )
+    .unwrap();
+
+    let (success, stdout) = check("oci", &layout);
+    assert!(success, "{stdout}");
+
+    fs::write(blobs.join(LAYER), "LAYER\n").unwrap();
+    fs::remove_file(blobs.join(CONFIG)).unwrap();
+
+    let (success, stdout) = check("oci", &layout);
+    assert!(!success);
+    assert_eq!(
+        stdout,
+        format!(
+            "image: FAILED\n  modified: blobs/sha256/{LAYER}\n  missing: blobs/sha256/{CONFIG}\nChecked 1 manifest, 1 failed.\n"
+        )
+    );
+}
+
+#[cfg(all(feature = "sha1", feature = "sha2"))]
+#[test]
+fn check_torrent() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = dir.path().join("data");
+
+    fs::create_dir_all(data.join("b")).unwrap();
+    fs::write(
+        data.join("a.bin"),
+        (0..40000).map(|i| (i % 251) as u8).collect::<Vec<_>>(),
+    )
+    .unwrap();
+    fs::write(data.join("b/c.txt"), "hello\n").unwrap();
+
+    let check_torrent = |torrent: &str| {
+        let out = dircs()
+            .args(["check", "torrent", torrent, "--root"])
+            .arg(dir.path())
+            .output()
+            .unwrap();
+
+        (out.status.success(), String::from_utf8(out.stdout).unwrap())
+    };
+
+    for torrent in ["./tests/torrents/v1.torrent", "./tests/torrents/v2.torrent"] {
+        let (success, stdout) = check_torrent(torrent);
+        assert!(success, "{stdout}");
+    }
+
+    // Change a byte in the middle of the second piece.
+    let mut a = fs::read(data.join("a.bin")).unwrap();
+    a[20000] ^= 1;
+    fs::write(data.join("a.bin"), a).unwrap();
+    fs::remove_file(data.join("b/c.txt")).unwrap();
+
+    for torrent in ["./tests/torrents/v1.torrent", "./tests/torrents/v2.torrent"] {
+        let (success, stdout) = check_torrent(torrent);
+        assert!(!success);
+        assert_eq!(
+            stdout,
+            "data: FAILED\n  modified: a.bin\n  missing: b/c.txt\nChecked 1 manifest, 1 failed.\n"
+        );
+    }
+}
+
+#[cfg(all(feature = "sha1", feature = "sha2"))]
+#[test]
+fn check_malformed_torrent() {
+    let dir = tempfile::tempdir().unwrap();
+    let pieces = format!("6:pieces20:{}", "x".repeat(20));
+    let huge_file = "d6:lengthi9223372036854775807e4:pathl1:aee";
+
+    for (info, error) in [
+        (
+            format!("6:lengthi5e4:name4:data12:piece lengthi3e{pieces}"),
+            "invalid piece length 3",
+        ),
+        (
+            format!("6:lengthi5e4:name4:data12:piece lengthi4611686018427387904e{pieces}"),
+            "invalid piece length 4611686018427387904",
+        ),
+        (
+            format!(
+                "5:filesl{huge_file}{huge_file}{huge_file}e4:name4:data12:piece lengthi16384e{pieces}"
+            ),
+            "the files are too large",
+        ),
+    ] {
+        let torrent = dir.path().join("malformed.torrent");
+        fs::write(&torrent, format!("d4:infod{info}ee")).unwrap();
+
+        let out = dircs()
+            .args(["check", "torrent"])
+            .arg(&torrent)
+            .output()
+            .unwrap();
+
+        assert!(!out.status.success());
+        assert!(String::from_utf8(out.stderr).unwrap().contains(error));
+    }
+}
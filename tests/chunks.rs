@@ -0,0 +1,59 @@
+#![allow(missing_docs)]
+
+use std::process::Command;
+
+fn dircs() -> Command {
+    let exe = env!("CARGO_BIN_EXE_dircs");
+    Command::new(exe)
+}
+
+#[test]
+fn chunks() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // Pseudo-random contents, so that the chunker finds boundaries.
+    let mut state = 1u64;
+    let contents = (0..1_000_000)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        })
+        .collect::<Vec<_>>();
+    std::fs::write(dir.path().join("a.img"), &contents).unwrap();
+
+    let chunks = || {
+        let out = dircs()
+            .args(["chunks", "--avg-size", "4096"])
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let single = chunks();
+    assert!(single.contains("Total: 1000000 bytes"));
+    assert!(single.contains("Unique: 1000000 bytes"));
+    assert!(single.ends_with("Deduplication would save 0 bytes (0.0%).\n"));
+
+    // Inserting bytes at the start only changes the first chunk.
+    let mut shifted = b"header".to_vec();
+    shifted.extend_from_slice(&contents);
+    std::fs::write(dir.path().join("b.img"), &shifted).unwrap();
+
+    let output = chunks();
+    assert!(output.starts_with("Chunked 2 files, 0 failed.\n"));
+    assert!(output.contains("Total: 2000006 bytes"));
+
+    let saved = output
+        .split("save ")
+        .nth(1)
+        .and_then(|rest| rest.split(' ').next())
+        .unwrap()
+        .parse::<u64>()
+        .unwrap();
+    assert!(saved > 950_000, "{output}");
+}
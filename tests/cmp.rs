@@ -0,0 +1,62 @@
+#![allow(missing_docs)]
+
+use std::process::Command;
+
+fn dircs() -> Command {
+    let exe = env!("CARGO_BIN_EXE_dircs");
+    Command::new(exe)
+}
+
+#[test]
+fn cmp() {
+    let dir = tempfile::tempdir().unwrap();
+    let contents = (0..100_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(dir.path().join("a.img"), &contents).unwrap();
+
+    let mut changed = contents.clone();
+    changed[5] ^= 1;
+    changed[25_000] ^= 1;
+    changed[35_000] ^= 1;
+    changed[90_000] ^= 1;
+    std::fs::write(dir.path().join("b.img"), &changed).unwrap();
+    std::fs::write(dir.path().join("c.img"), &contents[..95_000]).unwrap();
+
+    let cmp = |b: &str, args: &[&str]| {
+        let out = dircs()
+            .current_dir(dir.path())
+            .args(["cmp", "a.img", b, "--block-size", "10000"])
+            .args(args)
+            .output()
+            .unwrap();
+
+        (out.status.success(), String::from_utf8(out.stdout).unwrap())
+    };
+
+    assert_eq!(
+        cmp("a.img", &[]),
+        (true, "a.img and a.img are identical.\n".to_string())
+    );
+    assert_eq!(
+        cmp("b.img", &[]),
+        (
+            false,
+            "differ: bytes 0-9999\n\
+             differ: bytes 20000-39999\n\
+             differ: bytes 90000-99999\n"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        cmp("b.img", &["--first"]),
+        (false, "differ: bytes 0-9999\n".to_string())
+    );
+    assert_eq!(
+        cmp("c.img", &[]),
+        (
+            false,
+            "sizes differ: a.img is 100000 bytes, c.img is 95000 bytes\n\
+             differ: bytes 90000-99999\n"
+                .to_string()
+        )
+    );
+}
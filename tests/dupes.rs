@@ -0,0 +1,144 @@
+#![allow(missing_docs)]
+
+use std::{fs, process::Command};
+
+fn dircs() -> Command {
+    let exe = env!("CARGO_BIN_EXE_dircs");
+    Command::new(exe)
+}
+
+#[test]
+fn dupes() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("a.txt"), "hello\n").unwrap();
+    fs::write(root.join("sub/a.txt"), "hello\n").unwrap();
+    fs::write(root.join("sub/b.txt"), "hello\n").unwrap();
+    fs::write(root.join("c.txt"), "world\n").unwrap();
+    fs::write(root.join("big.bin"), vec![7; 10_000]).unwrap();
+    fs::write(root.join("sub/big.bin"), vec![7; 10_000]).unwrap();
+    fs::write(root.join("empty1"), "").unwrap();
+    fs::write(root.join("empty2"), "").unwrap();
+
+    let out = dircs()
+        .current_dir(root)
+        .args(["dupes", "."])
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "2 files, 10000 bytes each (10000 bytes wasted):\n  \
+         ./big.bin\n  \
+         ./sub/big.bin\n\
+         \n\
+         3 files, 6 bytes each (12 bytes wasted):\n  \
+         ./a.txt\n  \
+         ./sub/a.txt\n  \
+         ./sub/b.txt\n\
+         \n\
+         Found 2 duplicate sets with 3 duplicate files, wasting 10012 bytes.\n"
+    );
+
+    let out = dircs()
+        .current_dir(root)
+        .args(["dupes", ".", "--json"])
+        .output()
+        .unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+
+    assert_eq!(report["duplicate_files"], 3);
+    assert_eq!(report["wasted_bytes"], 10012);
+    assert_eq!(report["sets"][1]["paths"][2], "./sub/b.txt");
+}
+
+#[cfg(unix)]
+#[test]
+fn dupes_ignores_hardlinks() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    fs::write(root.join("a.txt"), "hello\n").unwrap();
+    fs::hard_link(root.join("a.txt"), root.join("b.txt")).unwrap();
+
+    let out = dircs().arg("dupes").arg(root).output().unwrap();
+
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "Found 0 duplicate sets with 0 duplicate files, wasting 0 bytes.\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn dupes_link() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    fs::write(root.join("a.txt"), "hello\n").unwrap();
+    fs::write(root.join("b.txt"), "hello\n").unwrap();
+    fs::write(root.join("c.txt"), "hello\n").unwrap();
+    fs::set_permissions(root.join("c.txt"), fs::Permissions::from_mode(0o600)).unwrap();
+
+    let link = |args: &[&str]| {
+        let out = dircs()
+            .current_dir(root)
+            .args(["dupes", ".", "--link", "hardlink"])
+            .args(args)
+            .output()
+            .unwrap();
+
+        (out.status.success(), String::from_utf8(out.stdout).unwrap())
+    };
+    let inode = |name: &str| fs::metadata(root.join(name)).unwrap().ino();
+
+    let (success, output) = link(&["--dry-run"]);
+    assert!(!success);
+    assert!(output.ends_with("Would link 1 files, reclaiming 6 bytes, 1 failed.\n"));
+    assert_ne!(inode("a.txt"), inode("b.txt"));
+
+    // Files with different permissions aren't linked, as they'd lose them.
+    let (success, output) = link(&[]);
+    assert!(!success);
+    assert!(output.contains("Linked ./b.txt -> ./a.txt\n"));
+    assert!(
+        output.contains("Couldn't link ./c.txt: its permissions or owner differ from ./a.txt\n")
+    );
+    assert_eq!(inode("a.txt"), inode("b.txt"));
+    assert_ne!(inode("a.txt"), inode("c.txt"));
+    assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "hello\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn dupes_link_keeps_unrelated_files() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    fs::write(root.join("a.txt"), "hello\n").unwrap();
+    fs::write(root.join("b.txt"), "hello\n").unwrap();
+    fs::write(root.join("b.txt.dircs-link"), "precious user data\n").unwrap();
+
+    let out = dircs()
+        .current_dir(root)
+        .args(["dupes", ".", "--link", "hardlink"])
+        .output()
+        .unwrap();
+
+    assert!(out.status.success());
+    assert_eq!(
+        fs::metadata(root.join("a.txt")).unwrap().ino(),
+        fs::metadata(root.join("b.txt")).unwrap().ino()
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("b.txt.dircs-link")).unwrap(),
+        "precious user data\n"
+    );
+    assert_eq!(fs::read_dir(root).unwrap().count(), 3);
+}
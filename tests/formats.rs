@@ -0,0 +1,220 @@
+#![allow(missing_docs)]
+
+use std::process::Command;
+
+fn dircs() -> Command {
+    let exe = env!("CARGO_BIN_EXE_dircs");
+    Command::new(exe)
+}
+
+fn hash_with_format(path: &str, format: &str, args: &[&str]) -> String {
+    let out = dircs()
+        .arg(path)
+        .args(["--format", format])
+        .args(args)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+
+    stdout.rsplit_once(' ').unwrap().1.trim_end().to_string()
+}
+
+#[cfg(feature = "sha1")]
+#[test]
+fn git_format() {
+    assert_eq!(
+        hash_with_format("./tests/test_dir", "git", &[]),
+        "655c2dfc4bc437eb73d4350d93a3ea6ecc4ba986"
+    );
+    assert_eq!(
+        hash_with_format("./tests/test_dir/sub_dir", "git", &[]),
+        "192b546c826d803e88f748b6e370b8885aa346fe"
+    );
+    assert_eq!(
+        hash_with_format("./tests/test_dir/a.txt", "git", &[]),
+        "78981922613b2afb6025042ff6bd878ac1994e85"
+    );
+}
+
+#[cfg(all(feature = "sha1", unix))]
+#[test]
+fn git_format_matches_git() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    std::fs::create_dir_all(root.join("a/b")).unwrap();
+    std::fs::create_dir_all(root.join("empty")).unwrap();
+    std::fs::write(root.join("a/b/c.txt"), "c").unwrap();
+    std::fs::write(root.join("a.txt"), "a").unwrap();
+    std::fs::write(root.join("a-"), "a-").unwrap();
+    std::fs::write(root.join("run.sh"), "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(root.join("run.sh"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::os::unix::fs::symlink("a/b/c.txt", root.join("link")).unwrap();
+
+    let git = |args: &[&str]| {
+        let out = Command::new("git")
+            .current_dir(root)
+            .args([
+                "-c",
+                "user.name=dircs",
+                "-c",
+                "user.email=dircs@example.com",
+            ])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+
+        String::from_utf8(out.stdout)
+            .unwrap()
+            .trim_end()
+            .to_string()
+    };
+
+    git(&["init", "-q"]);
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "initial"]);
+
+    assert_eq!(
+        hash_with_format(root.to_str().unwrap(), "git", &[]),
+        git(&["rev-parse", "HEAD^{tree}"])
+    );
+}
+
+#[cfg(all(feature = "sha1", feature = "sha2"))]
+#[test]
+fn git_format_sha256() {
+    assert_eq!(
+        hash_with_format("./tests/test_dir/a.txt", "git", &["-f", "sha2-256"]),
+        "f8625e43f9e04f24291f77cdbe4c71b3c2a3b0003f60419b3ed06a058d766c8b"
+    );
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn nar_format() {
+    assert_eq!(
+        hash_with_format("./tests/test_dir", "nar", &[]),
+        "sha256-NJbwh4Mw3lGnDzrpDEBTrcvG4/2KCkJ4KPOwluu4MxE="
+    );
+    assert_eq!(
+        hash_with_format("./tests/test_dir/a.txt", "nar", &[]),
+        "sha256-knb9U7Sdy/YWj4gjrvFIevLuTiMEt+5kEtunmYK5CoY="
+    );
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn go_format() {
+    assert_eq!(
+        hash_with_format("./tests/test_dir", "go", &[]),
+        "h1:om5CAX9yFd6nded8J6mhqHe67MN1isuuITveaRS/KeY="
+    );
+    assert_eq!(
+        hash_with_format(
+            "./tests/test_dir",
+            "go",
+            &["--go-prefix", "example.com/m@v1.0.0"]
+        ),
+        "h1:XG1ceW9m2bXYpGXZlhvN/5CidZxSimqv8AVlCn0EGvw="
+    );
+
+    // The prefix only means something to the go format.
+    let out = dircs()
+        .args(["./tests/test_dir", "--format", "nar", "--go-prefix", "x"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn fs_verity_format() {
+    let out = dircs()
+        .arg("./tests/test_dir")
+        .args(["--format", "fs-verity"])
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "./tests/test_dir/a.txt -> sha256:bbed9f07e45cbbf9b7570cf3b782a7977594d4c0e650b5125aa7075d524a788b\n\
+         ./tests/test_dir/b.txt -> sha256:d66868487ad0982474fa2a909c6d4ef76055f66aaf1e047239bdc1468f62ff57\n\
+         ./tests/test_dir/sub_dir/c.txt -> sha256:bf3daa36f55b63e0a5233770dfe14bf7449529af5c773be93d5d14a9efdd1486\n\
+         ./tests/test_dir/sub_dir/d.txt -> sha256:0110ec4efd22b8da55a2922e4172514033ae33e980eb65f074e82248bf37ccd2\n"
+    );
+
+    assert_eq!(
+        hash_with_format("./tests/test_dir/a.txt", "fs-verity", &["-f", "sha2-512"]),
+        "sha512:7b0c36bdfd7ae6d12a901bd31c1fbaab26a7017b751ae911c57ac52cc9f683ce2c7403b063becc9a268316a797344619db65ac5d22f0e09869ea0345083d8782"
+    );
+
+    // Large enough for a Merkle tree with two levels.
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("large");
+    let contents = (0..1_000_000u32)
+        .map(|i| ((i * 7 + 1_000_000) % 256) as u8)
+        .collect::<Vec<_>>();
+    std::fs::write(&file, contents).unwrap();
+
+    for args in [&[][..], &["--memmap"]] {
+        assert_eq!(
+            hash_with_format(file.to_str().unwrap(), "fs-verity", args),
+            "sha256:5275bc6934261241c4b0de1039f5dd2285d840d411abfa3dc88476494047c7ee"
+        );
+    }
+}
+
+#[test]
+fn structure_format() {
+    assert_eq!(
+        hash_with_format("./tests/test_dir", "structure", &[]),
+        "ae6c420bfb49f383651e6a1c397e5528d432ee0e224985d883c00939442147dd"
+    );
+
+    // Contents don't matter, but sizes do.
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("sub_dir")).unwrap();
+
+    for (name, contents) in [
+        ("a.txt", "x\n"),
+        ("b.txt", "y\n"),
+        ("sub_dir/c.txt", "z\n"),
+        ("sub_dir/d.txt", "w\n"),
+    ] {
+        std::fs::write(root.join(name), contents).unwrap();
+    }
+
+    let root = root.to_str().unwrap();
+    assert_eq!(
+        hash_with_format(root, "structure", &[]),
+        "ae6c420bfb49f383651e6a1c397e5528d432ee0e224985d883c00939442147dd"
+    );
+
+    std::fs::write(dir.path().join("a.txt"), "xx\n").unwrap();
+    assert_ne!(
+        hash_with_format(root, "structure", &[]),
+        "ae6c420bfb49f383651e6a1c397e5528d432ee0e224985d883c00939442147dd"
+    );
+}
+
+#[test]
+fn dircs_only_options() {
+    let hash = |args: &[&str]| {
+        let out = dircs().arg("./tests/test_dir").args(args).output().unwrap();
+
+        (out.status.success(), String::from_utf8(out.stdout).unwrap())
+    };
+
+    // Asking for the dircs format explicitly is the same as the default.
+    let metadata = ["--include-metadata", "mode"];
+    assert_eq!(
+        hash(&[&["--format", "dircs"], &metadata[..]].concat()),
+        hash(&metadata)
+    );
+    assert!(hash(&metadata).0);
+    assert!(!hash(&[&["--format", "structure"], &metadata[..]].concat()).0);
+}
@@ -0,0 +1,127 @@
+#![allow(missing_docs)]
+#![cfg(feature = "git")]
+
+use std::{path::Path, process::Command};
+
+fn dircs() -> Command {
+    let exe = env!("CARGO_BIN_EXE_dircs");
+    Command::new(exe)
+}
+
+fn hash(path: &Path, args: &[&str]) -> String {
+    let out = dircs().arg(path).args(args).output().unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+
+    stdout.rsplit_once(' ').unwrap().1.trim_end().to_string()
+}
+
+fn git(repo: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .current_dir(repo)
+        .args([
+            "-c",
+            "user.name=dircs",
+            "-c",
+            "user.email=dircs@example.com",
+        ])
+        .args(args)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+}
+
+fn setup_repo() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    std::fs::create_dir_all(root.join("sub_dir")).unwrap();
+    std::fs::write(root.join("a.txt"), "a\n").unwrap();
+    std::fs::write(root.join("b.txt"), "b\n").unwrap();
+    std::fs::write(root.join("sub_dir/c.txt"), "c\n").unwrap();
+    std::fs::write(root.join("sub_dir/d.txt"), "d\n").unwrap();
+
+    git(root, &["init", "-q"]);
+    git(root, &["add", "."]);
+    git(root, &["commit", "-q", "-m", "initial"]);
+
+    dir
+}
+
+#[test]
+fn git_tracked() {
+    let repo = setup_repo();
+    std::fs::write(repo.path().join("untracked.txt"), "untracked").unwrap();
+
+    // Same contents as the test directory, without the `.git` directory or untracked file.
+    assert_eq!(
+        hash(repo.path(), &["--git-tracked"]),
+        "da3e3c42d529bc285a65ff1bfe6a220e31c631185afabde6e63dffa4b1c55142"
+    );
+}
+
+#[test]
+fn git_rev() {
+    let repo = setup_repo();
+    std::fs::write(repo.path().join("a.txt"), "modified").unwrap();
+    std::fs::write(repo.path().join("untracked.txt"), "untracked").unwrap();
+
+    assert_eq!(
+        hash(repo.path(), &["--git-rev", "HEAD"]),
+        "da3e3c42d529bc285a65ff1bfe6a220e31c631185afabde6e63dffa4b1c55142"
+    );
+    assert_eq!(
+        hash(repo.path(), &["--git-rev", "HEAD", "--format", "dircs"]),
+        "da3e3c42d529bc285a65ff1bfe6a220e31c631185afabde6e63dffa4b1c55142"
+    );
+    assert_eq!(
+        hash(&repo.path().join("sub_dir"), &["--git-rev", "HEAD"]),
+        "d364677c85f04e475fc6a041d8cb4c54c4dcc3d93161d162d8db8c89f7598749"
+    );
+
+    git(repo.path(), &["commit", "-q", "-a", "-m", "modify"]);
+
+    assert_ne!(
+        hash(repo.path(), &["--git-rev", "HEAD"]),
+        hash(repo.path(), &["--git-rev", "HEAD~1"])
+    );
+}
+
+#[test]
+fn git_rev_deleted_path() {
+    let repo = setup_repo();
+    let a = hash(&repo.path().join("a.txt"), &[]);
+    let sub_dir = hash(&repo.path().join("sub_dir"), &[]);
+
+    // Paths only need to exist in the revision, not in the working directory.
+    std::fs::remove_file(repo.path().join("a.txt")).unwrap();
+    git(repo.path(), &["rm", "-q", "-r", "sub_dir"]);
+    git(repo.path(), &["commit", "-q", "-a", "-m", "delete"]);
+
+    assert_eq!(
+        hash(&repo.path().join("a.txt"), &["--git-rev", "HEAD~1"]),
+        a
+    );
+    assert_eq!(
+        hash(&repo.path().join("sub_dir"), &["--git-rev", "HEAD~1"]),
+        sub_dir
+    );
+    assert_eq!(
+        hash(
+            &repo.path().join("sub_dir/../a.txt"),
+            &["--git-rev", "HEAD~1"]
+        ),
+        a
+    );
+
+    let out = dircs()
+        .arg(repo.path().join("a.txt"))
+        .args(["--git-rev", "HEAD"])
+        .output()
+        .unwrap();
+    assert!(
+        String::from_utf8(out.stdout)
+            .unwrap()
+            .contains("does not exist in `HEAD`")
+    );
+}
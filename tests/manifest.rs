@@ -0,0 +1,72 @@
+#![allow(missing_docs)]
+
+use std::{fs, process::Command, time::SystemTime};
+
+fn dircs() -> Command {
+    let exe = env!("CARGO_BIN_EXE_dircs");
+    Command::new(exe)
+}
+
+#[test]
+fn manifest_status() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("files");
+    let manifest = dir.path().join("manifest");
+
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("a.txt"), "a\n").unwrap();
+    fs::write(root.join("b.txt"), "b\n").unwrap();
+    fs::write(root.join("sub/c.txt"), "c\n").unwrap();
+
+    let out = dircs()
+        .arg("manifest")
+        .arg(&root)
+        .arg("-o")
+        .arg(&manifest)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let status = |confirm: bool| {
+        let mut command = dircs();
+        command.arg("status").arg(&manifest).arg(&root);
+
+        if confirm {
+            command.arg("--confirm");
+        }
+
+        let out = command.output().unwrap();
+        (out.status.success(), String::from_utf8(out.stdout).unwrap())
+    };
+
+    assert_eq!(status(false), (true, "No changes.\n".to_string()));
+
+    // Only the metadata of `a.txt` changes, so confirming should rule it out.
+    fs::File::options()
+        .write(true)
+        .open(root.join("a.txt"))
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    fs::write(root.join("sub/c.txt"), "changed\n").unwrap();
+    fs::remove_file(root.join("b.txt")).unwrap();
+    fs::write(root.join("d.txt"), "d\n").unwrap();
+
+    assert_eq!(
+        status(false),
+        (
+            false,
+            "modified: a.txt\nmodified: sub/c.txt\ndeleted: b.txt\nnew: d.txt\n\
+             2 modified, 1 deleted, 1 new.\n"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        status(true),
+        (
+            false,
+            "modified: sub/c.txt\ndeleted: b.txt\nnew: d.txt\n1 modified, 1 deleted, 1 new.\n"
+                .to_string()
+        )
+    );
+}
@@ -0,0 +1,67 @@
+#![cfg(unix)]
+#![allow(missing_docs)]
+
+use std::{fs, process::Command};
+
+fn dircs() -> Command {
+    let exe = env!("CARGO_BIN_EXE_dircs");
+    Command::new(exe)
+}
+
+#[test]
+fn tag_and_scrub() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("a.txt"), "a\n").unwrap();
+    fs::write(root.join("sub/b.txt"), "b\n").unwrap();
+
+    // Not every filesystem supports user extended attributes.
+    if xattr::set(root.join("a.txt"), "user.dircs.test", b"1").is_err() {
+        return;
+    }
+
+    let run = |command: &str| {
+        let out = dircs().arg(command).arg(root).output().unwrap();
+        (out.status.success(), String::from_utf8(out.stdout).unwrap())
+    };
+
+    let (success, stdout) = run("tag");
+    assert!(success, "{stdout}");
+    assert_eq!(stdout, "Tagged 2 files, 0 failed.\n");
+
+    let (success, stdout) = run("scrub");
+    assert!(success, "{stdout}");
+    assert_eq!(
+        stdout,
+        "Scrubbed 2 files: 0 corrupted, 0 changed since tagged, 0 untagged, 0 failed.\n"
+    );
+
+    // Corrupt a file without changing its size or modification time.
+    let mtime = fs::metadata(root.join("a.txt"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    fs::write(root.join("a.txt"), "x\n").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(root.join("a.txt"))
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+
+    // Legitimate changes shouldn't be reported as corruption.
+    fs::write(root.join("sub/b.txt"), "changed\n").unwrap();
+    fs::write(root.join("new.txt"), "new\n").unwrap();
+
+    let (success, stdout) = run("scrub");
+    assert!(!success);
+    assert_eq!(
+        stdout,
+        format!(
+            "CORRUPTED: {}\nScrubbed 1 files: 1 corrupted, 1 changed since tagged, 1 untagged, 0 failed.\n",
+            root.join("a.txt").to_string_lossy()
+        )
+    );
+}